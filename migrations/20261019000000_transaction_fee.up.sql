@@ -0,0 +1,2 @@
+-- Add up migration script here
+alter table transaction_data add column fee bigint not null default 0;
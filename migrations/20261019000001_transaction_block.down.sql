@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop index transaction_data_block_height;
+
+alter table transaction_data drop column position;
+alter table transaction_data drop column block_height;
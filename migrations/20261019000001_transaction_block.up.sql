@@ -0,0 +1,5 @@
+-- Add up migration script here
+alter table transaction_data add column block_height bigint not null default 0;
+alter table transaction_data add column position bigint not null default 0;
+
+create index transaction_data_block_height on transaction_data (block_height, position);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+drop index transaction_data_to_address;
+drop index transaction_data_from_address;
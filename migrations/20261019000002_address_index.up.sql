@@ -0,0 +1,3 @@
+-- Add up migration script here
+create index transaction_data_from_address on transaction_data (from_address, block_height);
+create index transaction_data_to_address on transaction_data (to_address, block_height);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+alter table block_data drop column signature;
+alter table block_data drop column producer;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+alter table block_data add column producer varchar(64);
+alter table block_data add column signature varchar(128);
@@ -0,0 +1,6 @@
+-- Add up migration script here
+create table chain_metadata (
+	key varchar(64) not null,
+	value text not null,
+	primary key (key)
+);
@@ -0,0 +1,2 @@
+-- Add up migration script here
+alter table block_data add column version integer not null default 1;
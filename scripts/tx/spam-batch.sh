@@ -0,0 +1,19 @@
+#!/bin/bash
+
+read -p "tx count: " count
+
+# build one NDJSON line per transaction
+BATCH=""
+for (( i = 0; i < $count; i++ ))
+do
+	RAND_FROM=$(openssl rand -hex 32)
+	RAND_TO=$(openssl rand -hex 32)
+	RAND_INSTRUCTION=$(openssl rand -hex 32)
+
+	BATCH+='{"from": "'$RAND_FROM'", "to": "'$RAND_TO'", "instruction": "'$RAND_INSTRUCTION'"}'$'\n'
+done
+
+curl --location --request PUT "localhost:8080/api/transaction/batch" \
+	--header "Content-Type: application/x-ndjson" \
+	--data-binary "$BATCH"
+echo
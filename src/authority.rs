@@ -0,0 +1,256 @@
+use anyhow::{bail, Result};
+use std::time::Duration;
+
+use crate::config::Configuration;
+use crate::hasher::adopt_hash_algorithm;
+use crate::keys::{check_signature, parse_public_key};
+use crate::persistence::{models::BlockData, NodePersistency};
+use crate::replication::{fetch_blocks, REPLICATION_PAGE_SIZE};
+use crate::verifier::ChainVerifier;
+
+// longest a request to a peer may take, a slow peer delays the next turn
+const PEER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Producers of a proof-of-authority chain, they take turns
+/// sealing blocks by height in the configured order
+#[derive(Clone, Debug)]
+pub struct Producers {
+    // hex encoded public keys, lowercase
+    keys: Vec<String>,
+}
+
+impl Producers {
+    pub fn new(keys: &[String]) -> Result<Self> {
+        let mut normalized: Vec<String> = vec![];
+        for key in keys {
+            let key = key.trim().to_lowercase();
+            parse_public_key(&key)?;
+            if normalized.contains(&key) {
+                bail!("producer {key} is listed twice");
+            }
+            normalized.push(key);
+        }
+
+        if normalized.is_empty() {
+            bail!("a proof-of-authority chain needs at least one producer");
+        }
+
+        Ok(Self { keys: normalized })
+    }
+
+    /// Public keys, in turn order
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.keys.iter().any(|producer| producer == key)
+    }
+
+    /// Public key of the producer whose turn it is at `height`,
+    /// the first producer seals genesis
+    pub fn producer_for(&self, height: u32) -> &str {
+        let turn = height.saturating_sub(1) as usize % self.keys.len();
+        &self.keys[turn]
+    }
+
+    /// Checks `block` is signed by the producer whose turn it was,
+    /// the error is the reason it isn't
+    pub fn check(&self, block: &BlockData) -> Result<(), String> {
+        let expected = self.producer_for(block.height);
+        match &block.producer {
+            Some(producer) if producer == expected => check_signature(block),
+            Some(producer) => Err(format!(
+                "produced by {producer}, but it's the turn of {expected}"
+            )),
+            None => Err(format!("unsigned, but it's the turn of {expected}")),
+        }
+    }
+}
+
+/// Pulls the blocks other producers sealed past the local tip from `peers`,
+/// checking linkage, hashes and that each one was sealed in turn
+pub struct PeerSync {
+    client: reqwest::Client,
+    peers: Vec<String>,
+    producers: Producers,
+}
+
+impl PeerSync {
+    pub fn new(peers: &[String], producers: Producers) -> Result<Self> {
+        let client = reqwest::Client::builder().timeout(PEER_TIMEOUT).build()?;
+
+        Ok(Self {
+            client,
+            peers: peers.to_vec(),
+            producers,
+        })
+    }
+
+    /// Stores and publishes every valid block the peers have past the local tip,
+    /// unreachable peers and invalid blocks are skipped
+    pub async fn sync(&self, config: &Configuration) -> Result<()> {
+        for peer in &self.peers {
+            while self.sync_page(config, peer).await? {}
+        }
+        Ok(())
+    }
+
+    // returns whether `peer` may have more blocks
+    async fn sync_page(&self, config: &Configuration, peer: &str) -> Result<bool> {
+        let snapshot = config.chain.snapshot();
+        let page = match fetch_blocks(&self.client, peer, snapshot.height() + 1).await {
+            Ok(page) => page,
+            Err(err) => {
+                println!("Failed to reach peer {peer}: {err:#}");
+                return Ok(false);
+            }
+        };
+        // a new chain without a hash algorithm adopts the first peer's with its blocks
+        let Some(offered) = page.hash_algorithm() else {
+            return Ok(false);
+        };
+        let algorithm = adopt_hash_algorithm(config, offered).await?;
+        if offered != algorithm {
+            println!(
+                "Ignored peer {peer}: it hashes with {offered}, the local chain with {algorithm}"
+            );
+            return Ok(false);
+        }
+
+        let mut verifier = match snapshot.tip() {
+            Some(tip) => ChainVerifier::resume(tip.clone(), algorithm),
+            None => ChainVerifier::new(algorithm),
+        }
+        .with_producers(Some(self.producers.clone()));
+
+        let full_page = page.blocks.len() == REPLICATION_PAGE_SIZE as usize;
+        for sealed in page.blocks {
+            if let Err(err) = verifier.check(&sealed.block, &sealed.transactions) {
+                println!("Rejected block from peer {peer}: {err}");
+                return Ok(false);
+            }
+            config
+                .persistence
+                .store_sealed_block_data(&sealed.block, &sealed.transactions)
+                .await?;
+            config.chain.publish_sealed_block(sealed);
+        }
+
+        Ok(full_page)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::BLOCK_HEADER_VERSION;
+    use crate::hasher::HashAlgorithm;
+    use crate::keys::NodeKey;
+
+    fn keys() -> [NodeKey; 3] {
+        [1, 2, 3].map(|secret| NodeKey::from_secret([secret; 32]))
+    }
+
+    fn producers(keys: &[NodeKey]) -> Producers {
+        let public_keys: Vec<String> = keys.iter().map(NodeKey::public_key).collect();
+        Producers::new(&public_keys).unwrap()
+    }
+
+    // an empty block at `height` after `prev`, signed by `key`
+    fn signed_block(height: u32, prev: Option<&BlockData>, key: &NodeKey) -> BlockData {
+        let id = format!("0192a0c4-8e00-7000-8000-{height:012}");
+        let prev_block_hash = prev.map_or(String::new(), |prev| prev.hash.clone());
+        let mut block = BlockData {
+            version: BLOCK_HEADER_VERSION,
+            hash: BlockData::get_hash(HashAlgorithm::Sha256, &id, 0, height, &prev_block_hash, &[]),
+            id,
+            nonce: 0,
+            height,
+            prev_block_hash,
+            producer: None,
+            signature: None,
+        };
+        key.sign(&mut block);
+        block
+    }
+
+    #[test]
+    fn producers_take_turns_by_height() {
+        let keys = keys();
+        let producers = producers(&keys);
+
+        let turns: Vec<&str> = (1..=7)
+            .map(|height| producers.producer_for(height))
+            .collect();
+        let expected: Vec<String> = [0, 1, 2, 0, 1, 2, 0]
+            .iter()
+            .map(|turn| keys[*turn].public_key())
+            .collect();
+        assert_eq!(turns, expected);
+    }
+
+    #[test]
+    fn accepts_blocks_signed_in_turn() {
+        let keys = keys();
+        let producers = producers(&keys);
+
+        let genesis = signed_block(1, None, &keys[0]);
+        let second = signed_block(2, Some(&genesis), &keys[1]);
+
+        assert_eq!(producers.check(&genesis), Ok(()));
+        assert_eq!(producers.check(&second), Ok(()));
+    }
+
+    #[test]
+    fn rejects_blocks_signed_out_of_turn() {
+        let keys = keys();
+        let producers = producers(&keys);
+        let genesis = signed_block(1, None, &keys[0]);
+
+        // the first producer again, the second one's turn
+        let out_of_turn = signed_block(2, Some(&genesis), &keys[0]);
+        let reason = producers.check(&out_of_turn).unwrap_err();
+        assert!(reason.contains(&format!("but it's the turn of {}", keys[1].public_key())));
+
+        // a key that isn't a producer at all
+        let outsider = NodeKey::from_secret([9; 32]);
+        assert!(producers
+            .check(&signed_block(2, Some(&genesis), &outsider))
+            .is_err());
+
+        let mut unsigned = signed_block(2, Some(&genesis), &keys[1]);
+        unsigned.producer = None;
+        unsigned.signature = None;
+        assert!(producers
+            .check(&unsigned)
+            .unwrap_err()
+            .starts_with("unsigned"));
+
+        // in turn, but the signature is another block's
+        let mut forged = signed_block(2, Some(&genesis), &keys[1]);
+        forged.signature = signed_block(3, Some(&genesis), &keys[1]).signature;
+        assert!(producers.check(&forged).is_err());
+    }
+
+    #[test]
+    fn verifier_rejects_blocks_sealed_out_of_turn() {
+        let keys = keys();
+        let mut verifier =
+            ChainVerifier::new(HashAlgorithm::Sha256).with_producers(Some(producers(&keys)));
+
+        let genesis = signed_block(1, None, &keys[0]);
+        verifier.check(&genesis, &[]).unwrap();
+        let second = signed_block(2, Some(&genesis), &keys[1]);
+        verifier.check(&second, &[]).unwrap();
+
+        let out_of_turn = signed_block(3, Some(&second), &keys[1]);
+        let err = verifier.check(&out_of_turn, &[]).unwrap_err();
+        assert!(format!("{err:#}").contains("turn of"));
+        assert_eq!(verifier.tip().map(|tip| tip.height), Some(2));
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::persistence::{sqlite::SqlitePersistence, NodePersistency};
+use crate::verifier::verify_chain;
+
+/// Details of a backup, stored next to it as `<backup>.json`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BackupManifest {
+    /// File name of the backup, in the same directory as the manifest
+    pub file: String,
+    /// Tip of the chain in the backup
+    pub height: u32,
+    pub hash: String,
+    pub created_at: String,
+    /// Checksum of the backup file
+    pub sha256: String,
+}
+
+/// Takes a consistent snapshot of the live database into `dir`,
+/// named and tagged after the tip it holds
+pub async fn create(persistence: &SqlitePersistence, dir: &Path) -> Result<BackupManifest> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("failed to create backup directory {}", dir.display()))?;
+
+    // the tip is only known once the snapshot is taken
+    let partial = dir.join(format!(".{}.partial", Uuid::now_v7()));
+    persistence.vacuum_into(&partial).await?;
+
+    // read the tip from the snapshot itself, blocks sealed since aren't in it
+    let tip = {
+        let snapshot = SqlitePersistence::open_read_only(&partial).await?;
+        let tip = snapshot.read_latest_block_data().await;
+        snapshot.pool.close().await;
+        tip
+    };
+    let tip = match tip {
+        Ok(tip) => tip,
+        Err(err) => {
+            let _ = fs::remove_file(&partial);
+            return Err(err);
+        }
+    };
+
+    // imported and legacy chains may hold shorter hashes
+    let short_hash = tip.hash.get(..12).unwrap_or(&tip.hash);
+    let file = format!("blocks-{}-{short_hash}.db", tip.height);
+    let path = dir.join(&file);
+    fs::rename(&partial, &path)?;
+
+    let manifest = BackupManifest {
+        file,
+        height: tip.height,
+        hash: tip.hash,
+        created_at: chrono::Utc::now()
+            .format("%Y-%m-%d %H:%M:%S UTC")
+            .to_string(),
+        sha256: sha256::try_digest(path.as_path())?,
+    };
+    fs::write(manifest_path(&path), serde_json::to_vec_pretty(&manifest)?)?;
+
+    Ok(manifest)
+}
+
+/// Checks the backup at `path` against its manifest: checksum,
+/// tip, and every block and transaction hash from genesis
+pub async fn check(path: &Path) -> Result<BackupManifest> {
+    let manifest_path = manifest_path(path);
+    let manifest: BackupManifest = serde_json::from_slice(
+        &fs::read(&manifest_path)
+            .with_context(|| format!("failed to read manifest {}", manifest_path.display()))?,
+    )
+    .with_context(|| format!("invalid manifest {}", manifest_path.display()))?;
+
+    if sha256::try_digest(path)? != manifest.sha256 {
+        bail!(
+            "backup {} doesn't match its manifest checksum",
+            path.display()
+        );
+    }
+
+    let snapshot = SqlitePersistence::open_read_only(path).await?;
+    let verified = verify_chain(&snapshot, None).await;
+    snapshot.pool.close().await;
+    let verifier = verified?;
+
+    // verify_chain fails on an empty chain
+    let tip = verifier.tip().unwrap();
+    if tip.height != manifest.height || tip.hash != manifest.hash {
+        bail!(
+            "backup tip is at height {} ({}), its manifest says height {} ({})",
+            tip.height,
+            tip.hash,
+            manifest.height,
+            manifest.hash
+        );
+    }
+
+    Ok(manifest)
+}
+
+// `blocks-1-abc.db` -> `blocks-1-abc.db.json`
+fn manifest_path(path: &Path) -> PathBuf {
+    let mut manifest_path = path.as_os_str().to_owned();
+    manifest_path.push(".json");
+    PathBuf::from(manifest_path)
+}
@@ -1,15 +1,26 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
 use tokio::time;
 use uuid::Uuid;
 
+use crate::authority::{PeerSync, Producers};
+use crate::config::{BlockTrigger, Configuration, EmptyBlockPolicy};
+use crate::encoding::BLOCK_HEADER_VERSION;
+use crate::hasher::HashAlgorithm;
+use crate::keys::NodeKey;
+use crate::mempool::{RejectedTransactions, TransactionPool};
 use crate::persistence::{
     models::{BlockData, TransactionData},
     sqlite::SqlitePersistence,
     NodePersistency,
 };
+use crate::snapshot::{ChainSnapshot, RECENT_BLOCKS_CAPACITY};
 use crate::utils::get_random_nonce;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -18,123 +29,617 @@ pub struct Transaction {
     pub to: String,
     #[serde(with = "serde_bytes")]
     pub instruction: Vec<u8>,
+    /// Priority of the transaction in the pool, higher is sealed first
+    #[serde(default)]
+    pub fee: u32,
+}
+
+impl Transaction {
+    /// Size of the [`Transaction`] in bytes,
+    /// counted as the sum of its field lengths
+    pub fn size(&self) -> usize {
+        self.from.len() + self.to.len() + self.instruction.len()
+    }
+}
+
+/// A [`Transaction`] identified by its hash, as it waits in the pool
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PendingTransaction {
+    // uuidv7 with timestamp
+    pub id: String,
+    pub hash: String,
+    #[serde(flatten)]
+    pub transaction: Transaction,
+}
+
+impl PendingTransaction {
+    /// Identifies `tx`, hashed with the chain's `algorithm`
+    pub fn new(tx: Transaction, algorithm: HashAlgorithm) -> Self {
+        let id = Uuid::now_v7().to_string();
+        let Transaction {
+            from,
+            to,
+            instruction,
+            fee,
+        } = &tx;
+        // generate hash
+        let hash = TransactionData::get_hash(algorithm, &id, from, to, instruction, *fee);
+
+        PendingTransaction {
+            id,
+            hash,
+            transaction: tx,
+        }
+    }
+}
+
+/// Where a [`Transaction`] is, looked up by its hash
+#[derive(Debug, Serialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum TransactionStatus {
+    /// Waiting in the pool, `position` is its place in the sealing order
+    Pending { position: usize },
+    /// Taken from the pool into a block that isn't published yet
+    Sealing,
+    /// Stored in the block at `block_height`, at `position` within the block
+    Sealed {
+        block_height: u32,
+        block_hash: String,
+        position: u32,
+    },
+    /// Refused by the factory
+    Rejected { reason: String },
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Block {
-    pub transactions: Vec<Transaction>,
+    pub transactions: Vec<PendingTransaction>,
 }
 
 impl Block {
-    pub fn from(tx_pool: Vec<Transaction>) -> Self {
+    pub fn from(tx_pool: Vec<PendingTransaction>) -> Self {
         Self {
             transactions: tx_pool,
         }
     }
 }
 
+/// A [`Block`] as it was stored by the [`Node`]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SealedBlock {
+    pub block: BlockData,
+    pub transactions: Vec<TransactionData>,
+}
+
+/// Limits applied when sealing blocks from the pool
+#[derive(Clone, Debug)]
+pub struct BlockLimits {
+    /// Maximum amount of transactions per block (`None` for unlimited)
+    pub max_transactions: Option<usize>,
+    /// Maximum total transaction bytes per block (`None` for unlimited)
+    pub max_bytes: Option<usize>,
+    /// Maximum amount of blocks sealed per block epoch
+    pub max_blocks_per_epoch: u32,
+}
+
+impl BlockLimits {
+    /// Returns true if a [`Transaction`] of `size` bytes can never fit in a block
+    pub fn exceeds_max_bytes(&self, size: usize) -> bool {
+        matches!(self.max_bytes, Some(max_bytes) if size > max_bytes)
+    }
+}
+
+/// Request for the node runner to seal the pool right away
+#[derive(Debug)]
+pub struct SealRequest {
+    /// Receives the heights of the sealed blocks (if the requester waits)
+    pub reply: Option<oneshot::Sender<Vec<u32>>>,
+}
+
+// pending seal requests, more than this means a seal is already queued
+const SEAL_QUEUE_SIZE: usize = 16;
+// longest an on-demand seal is waited for, the node runner may have stopped
+const SEAL_TIMEOUT: Duration = Duration::from_secs(30);
+// sealed blocks kept for slow subscribers before they lag
+const SEALED_BLOCKS_CAPACITY: usize = 64;
+
 pub struct Chain {
-    pub tx_pool: Arc<Mutex<Vec<Transaction>>>,
+    pub tx_pool: Arc<Mutex<TransactionPool>>,
+    // recently refused transactions, for status lookups
+    rejected: Mutex<RejectedTransactions>,
+    // drained from the pool, until their block is published
+    sealing: std::sync::Mutex<HashMap<String, PendingTransaction>>,
+    seal_sender: mpsc::Sender<SealRequest>,
+    // taken by the node runner for as long as it runs
+    seal_receiver: Mutex<mpsc::Receiver<SealRequest>>,
+    // automatic triggers are ignored while paused
+    paused: AtomicBool,
+    // can change while the node runner runs
+    block_time: watch::Sender<Duration>,
+    // unset until a new chain pulling from other nodes adopts theirs
+    hash_algorithm: watch::Sender<Option<HashAlgorithm>>,
+    // every block sealed by the node runner, after it's committed
+    sealed_blocks: broadcast::Sender<Arc<SealedBlock>>,
+    // tip and recent blocks, replaced as a whole on every new block
+    snapshot: watch::Sender<Arc<ChainSnapshot>>,
 }
 
 impl Chain {
-    pub fn new() -> Self {
+    pub fn new(block_time: Duration, hash_algorithm: Option<HashAlgorithm>) -> Self {
+        let (seal_sender, seal_receiver) = mpsc::channel(SEAL_QUEUE_SIZE);
         Self {
-            tx_pool: Arc::new(Mutex::new(vec![])),
+            tx_pool: Arc::new(Mutex::new(TransactionPool::new())),
+            rejected: Mutex::new(RejectedTransactions::new()),
+            sealing: std::sync::Mutex::new(HashMap::new()),
+            seal_sender,
+            seal_receiver: Mutex::new(seal_receiver),
+            paused: AtomicBool::new(false),
+            block_time: watch::Sender::new(block_time),
+            hash_algorithm: watch::Sender::new(hash_algorithm),
+            sealed_blocks: broadcast::Sender::new(SEALED_BLOCKS_CAPACITY),
+            snapshot: watch::Sender::new(Arc::new(ChainSnapshot::default())),
         }
     }
 
-    /// Creates a [`Block`] from the transaction pool,
-    /// empties the pool, and returns the block
-    pub async fn create_block_from_pool(&self) -> Result<Block> {
-        let arc_mutex_pool: Arc<Mutex<Vec<Transaction>>> = Arc::clone(&self.tx_pool);
-        let pool = arc_mutex_pool.lock().await;
-        let block = Block::from(pool.to_vec());
-        // clear tx pool
-        Ok(block)
+    /// Remembers why the [`PendingTransaction`] with `hash` was refused
+    pub async fn reject(&self, hash: String, reason: String) {
+        self.rejected.lock().await.insert(hash, reason);
     }
 
-    pub async fn clear_pool(&self) -> Result<()> {
-        let arc_mutex_pool: Arc<Mutex<Vec<Transaction>>> = Arc::clone(&self.tx_pool);
-        let mut pool = arc_mutex_pool.lock().await;
-        // clear tx pool
-        *pool = vec![];
+    /// Current [`ChainSnapshot`], a response reads from one snapshot
+    /// so it never mixes two tips
+    pub fn snapshot(&self) -> Arc<ChainSnapshot> {
+        Arc::clone(&self.snapshot.borrow())
+    }
+
+    /// Replaces the snapshot with the most recent stored blocks
+    pub async fn load_snapshot(&self, persistence: &SqlitePersistence) -> Result<()> {
+        let blocks = persistence
+            .read_recent_block_data(u32::MAX, RECENT_BLOCKS_CAPACITY as u32, 0)
+            .await?;
+
+        let mut recent = vec![];
+        // lowest height first
+        for block in blocks.into_iter().rev() {
+            let transactions = persistence
+                .read_block_transaction_data(block.height)
+                .await?;
+            recent.push(SealedBlock {
+                block,
+                transactions,
+            });
+        }
+
+        self.snapshot
+            .send_replace(Arc::new(ChainSnapshot::from_blocks(recent)));
         Ok(())
     }
+
+    /// Looks up the [`TransactionStatus`] of the transaction with `hash`
+    /// in the pool, the blocks being sealed, the recently rejected transactions,
+    /// then the stored blocks up to the current snapshot
+    pub async fn transaction_status(
+        &self,
+        persistence: &SqlitePersistence,
+        hash: &str,
+    ) -> Result<Option<TransactionStatus>> {
+        if let Some(position) = self.tx_pool.lock().await.position(hash) {
+            return Ok(Some(TransactionStatus::Pending { position }));
+        }
+        // drained after the pool lookup, published blocks are in the snapshot
+        if self.sealing.lock().unwrap().contains_key(hash) {
+            return Ok(Some(TransactionStatus::Sealing));
+        }
+
+        if let Some(reason) = self.rejected.lock().await.reason(hash) {
+            return Ok(Some(TransactionStatus::Rejected {
+                reason: reason.to_owned(),
+            }));
+        }
+
+        let snapshot = self.snapshot();
+        let Some(tx_data) = persistence.read_transaction_data_by_hash(hash).await? else {
+            return Ok(None);
+        };
+        // stored after the snapshot, its block is about to be published
+        if tx_data.block_height > snapshot.height() {
+            return Ok(Some(TransactionStatus::Sealing));
+        }
+
+        let block_hash = match snapshot.block(tx_data.block_height) {
+            Some(sealed) => sealed.block.hash.clone(),
+            None => match persistence
+                .read_block_data_by_height(tx_data.block_height)
+                .await?
+            {
+                Some(block_data) => block_data.hash,
+                None => return Ok(None),
+            },
+        };
+
+        Ok(Some(TransactionStatus::Sealed {
+            block_height: tx_data.block_height,
+            block_hash,
+            position: tx_data.position,
+        }))
+    }
+
+    /// The [`PendingTransaction`] with `hash`, if it's in the pool
+    /// or drained into a block that isn't published yet
+    pub async fn unpublished_transaction(&self, hash: &str) -> Option<PendingTransaction> {
+        if let Some(tx) = self.tx_pool.lock().await.get(hash) {
+            return Some(tx.clone());
+        }
+        self.sealing.lock().unwrap().get(hash).cloned()
+    }
+
+    /// Subscribes to blocks sealed from now on
+    pub fn subscribe_sealed_blocks(&self) -> broadcast::Receiver<Arc<SealedBlock>> {
+        self.sealed_blocks.subscribe()
+    }
+
+    /// Moves the snapshot to a newly stored block, then announces it
+    pub fn publish_sealed_block(&self, sealed_block: SealedBlock) {
+        let sealed_block = Arc::new(sealed_block);
+        // subscribers can read what they're told about
+        self.snapshot.send_modify(|snapshot| {
+            *snapshot = Arc::new(snapshot.with_block(Arc::clone(&sealed_block)));
+        });
+        // status lookups find them in the snapshot from now on
+        let mut sealing = self.sealing.lock().unwrap();
+        for tx in &sealed_block.transactions {
+            sealing.remove(&tx.hash);
+        }
+        // no subscribers is fine
+        let _ = self.sealed_blocks.send(sealed_block);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Pauses or resumes sealing on block time and pool size
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn block_time(&self) -> Duration {
+        *self.block_time.borrow()
+    }
+
+    /// Changes the block time of the running node runner
+    pub fn set_block_time(&self, block_time: Duration) {
+        self.block_time.send_replace(block_time);
+    }
+
+    /// Hash algorithm of the chain, `None` while a new chain
+    /// waits for the first blocks pulled from other nodes
+    pub fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        *self.hash_algorithm.borrow()
+    }
+
+    /// Settles the hash algorithm of a chain that had none
+    pub fn set_hash_algorithm(&self, hash_algorithm: HashAlgorithm) {
+        self.hash_algorithm.send_replace(Some(hash_algorithm));
+    }
+
+    /// Asks the node runner to seal the pool without waiting for it
+    pub fn request_seal(&self) {
+        // a full queue already has a seal pending, nothing to add
+        let _ = self.seal_sender.try_send(SealRequest { reply: None });
+    }
+
+    /// Asks the node runner to seal the pool,
+    /// and returns the heights of the sealed blocks
+    pub async fn seal_now(&self) -> Result<Vec<u32>> {
+        let (reply, sealed) = oneshot::channel();
+        let request = async {
+            self.seal_sender
+                .send(SealRequest { reply: Some(reply) })
+                .await?;
+            Ok(sealed.await?)
+        };
+
+        match time::timeout(SEAL_TIMEOUT, request).await {
+            Ok(heights) => heights,
+            Err(_) => bail!(
+                "node runner didn't seal within {}",
+                humantime::format_duration(SEAL_TIMEOUT)
+            ),
+        }
+    }
+
+    /// Asks for a seal if the trigger seals on pool size and the pool reached `threshold`
+    pub async fn request_seal_if_full(&self, trigger: &BlockTrigger, threshold: usize) {
+        if !trigger.is_sized() {
+            return;
+        }
+
+        let pool_len = self.tx_pool.lock().await.len();
+        if pool_len >= threshold {
+            self.request_seal();
+        }
+    }
+
+    /// Takes the highest priority transactions that fit
+    /// within the [`BlockLimits`], and returns them as a [`Block`]
+    pub async fn drain_block_from_pool(&self, limits: &BlockLimits) -> Result<Block> {
+        let arc_mutex_pool: Arc<Mutex<TransactionPool>> = Arc::clone(&self.tx_pool);
+        let mut pool = arc_mutex_pool.lock().await;
+        // remove sealed transactions from the pool, the rest waits for the next block
+        let transactions = pool.drain_block(limits);
+        // still found by status lookups until their block is published
+        let mut sealing = self.sealing.lock().unwrap();
+        for tx in &transactions {
+            sealing.insert(tx.hash.clone(), tx.clone());
+        }
+        Ok(Block::from(transactions))
+    }
 }
 
 pub struct Node {
     persistence: SqlitePersistence,
+    // block nonces are drawn from 0..nonce_limit
+    nonce_limit: u32,
+    // settled before the node seals anything but genesis
+    hash_algorithm: watch::Receiver<Option<HashAlgorithm>>,
+    // signs every block sealed here, if set
+    key: Option<NodeKey>,
 }
 
 impl Node {
-    pub async fn new() -> Result<Self> {
-        Ok(Self {
-            persistence: SqlitePersistence::from_env().await?,
-        })
+    pub fn new(
+        persistence: SqlitePersistence,
+        nonce_limit: u32,
+        chain: &Chain,
+        key: Option<NodeKey>,
+    ) -> Self {
+        Self {
+            persistence,
+            nonce_limit,
+            hash_algorithm: chain.hash_algorithm.subscribe(),
+            key,
+        }
+    }
+
+    fn hash_algorithm(&self) -> Result<HashAlgorithm> {
+        match *self.hash_algorithm.borrow() {
+            Some(hash_algorithm) => Ok(hash_algorithm),
+            None => bail!("the chain has no hash algorithm yet"),
+        }
     }
 
     pub async fn store_genesis_block(&self) -> Result<()> {
-        let tx = self.persistence.pool.begin().await?;
+        // restarting on an existing chain
+        if self
+            .persistence
+            .read_block_data_by_height(1)
+            .await?
+            .is_some()
+        {
+            println!("Genesis block already stored. Skipping...");
+            return Ok(());
+        }
 
         // create block_data
         let id = Uuid::now_v7().to_string();
-        let nonce = get_random_nonce(100); // upper_limit = 100
+        let nonce = get_random_nonce(self.nonce_limit);
         let height = 1; // genesis is at height 1
         let prev_block_hash = String::from("");
 
-        // calculate hash
-        let hash = BlockData::get_sha256_hash(&id, &nonce, &height, &prev_block_hash);
+        // calculate hash, genesis has no transactions
+        let hash = BlockData::get_hash(
+            self.hash_algorithm()?,
+            &id,
+            nonce,
+            height,
+            &prev_block_hash,
+            &[],
+        );
 
-        let block_data = BlockData {
+        let mut block_data = BlockData {
+            version: BLOCK_HEADER_VERSION,
             hash,
             id,
             nonce,
             height,
             prev_block_hash,
+            producer: None,
+            signature: None,
         };
+        if let Some(key) = &self.key {
+            key.sign(&mut block_data);
+        }
 
         // store BlockData
-        self.persistence.store_block_data(block_data).await?;
-
-        Ok(tx.commit().await?)
+        self.persistence
+            .store_sealed_block_data(&block_data, &[])
+            .await
     }
 
     // chain runner
-    pub async fn run(&self, chain: &Chain, block_time: u32) -> Result<()> {
-        let pool = Arc::clone(&chain.tx_pool);
-        let mut interval = time::interval(time::Duration::from_secs(block_time as u64));
+    pub async fn run(&self, config: &Configuration) -> Result<()> {
+        let chain = &config.chain;
+        let trigger = &config.block_trigger;
+        // without a timed trigger, the block time still paces empty blocks
+        let empty_blocks = !matches!(config.empty_block_policy, EmptyBlockPolicy::Skip);
+
+        // genesis is stored, start from what's there
+        chain.load_snapshot(&self.persistence).await?;
+
+        let mut seal_requests = chain.seal_receiver.lock().await;
+        let mut block_time = chain.block_time.subscribe();
+        let mut interval = block_interval(*block_time.borrow_and_update());
         // deal with the first initial tick
-        interval.tick().await;
+        // genesis was just stored, count heartbeats from here
+        let mut last_block_at = interval.tick().await;
         loop {
-            interval.tick().await;
-            println!("End of block epoch. Processing...");
-
-            {
-                // limit lock to smallest possible block scope
-                let pool_size = pool.lock().await.len();
-                if pool_size == 0 {
-                    println!("No transaction in pool. Skipping...");
+            // wait for whichever trigger comes first
+            let (epoch_end, on_time, reply) = tokio::select! {
+                epoch_end = interval.tick(), if trigger.is_timed() || empty_blocks => {
+                    (epoch_end, true, None)
+                }
+                Some(request) = seal_requests.recv() => (time::Instant::now(), false, request.reply),
+                Ok(()) = block_time.changed() => {
+                    let new_block_time = *block_time.borrow_and_update();
+                    println!("Block time changed to {}", humantime::format_duration(new_block_time));
+                    // start a fresh epoch with the new block time
+                    interval = block_interval(new_block_time);
+                    interval.tick().await;
                     continue;
                 }
-            } // lock drops here
+            };
 
-            // create new block
-            let block = chain.create_block_from_pool().await?;
-            // empty transaction pool to []
-            chain.clear_pool().await?;
+            // automatic triggers wait while paused, on-demand seals don't
+            if reply.is_none() && chain.is_paused() {
+                println!("Factory paused. Skipping...");
+                continue;
+            }
 
-            // store_block
-            self.store_block(block).await?;
+            let empty_only = on_time && !trigger.is_timed();
+            let heights = self
+                .seal_epoch(config, epoch_end, empty_only, &mut last_block_at)
+                .await?;
+
+            if let Some(reply) = reply {
+                // requester may have given up waiting
+                let _ = reply.send(heights);
+            }
+
+            // a size or on-demand seal restarts the block time in hybrid mode
+            if !on_time && matches!(trigger, BlockTrigger::Hybrid) {
+                interval.reset();
+            }
+
+            // leftovers of size limited blocks can still be over the threshold
+            chain
+                .request_seal_if_full(trigger, config.seal_threshold)
+                .await;
         }
         // Ok(())
     }
 
-    /// Consumes a [`Block`] and returns its calculated hash
-    async fn store_block(&self, block: Block) -> Result<()> {
-        println!("begin tx");
-        let tx = self.persistence.pool.begin().await?;
+    /// Proof-of-authority runner: every block time, pulls what the other
+    /// producers sealed, then seals the next block if it's this node's turn
+    /// (even an empty one, the others wait for it). Turns never time out,
+    /// an offline producer stalls the chain at its height until it's back
+    pub async fn run_authority(&self, config: &Configuration, producers: &Producers) -> Result<()> {
+        let chain = &config.chain;
+        let Some(key) = &self.key else {
+            bail!("proof-of-authority nodes need a producer key");
+        };
+        let public_key = key.public_key();
+        let peers = PeerSync::new(&config.peers, producers.clone())?;
+
+        // the first producer seals genesis, the others pull it
+        if producers.producer_for(1) == public_key {
+            self.store_genesis_block().await?;
+        }
+        chain.load_snapshot(&self.persistence).await?;
+
+        let mut seal_requests = chain.seal_receiver.lock().await;
+        let mut block_time = chain.block_time.subscribe();
+        let mut interval = block_interval(*block_time.borrow_and_update());
+        loop {
+            // turns are timed, on-demand seals only hurry this node's own turn
+            let reply = tokio::select! {
+                _ = interval.tick() => None,
+                Some(request) = seal_requests.recv() => request.reply,
+                Ok(()) = block_time.changed() => {
+                    let new_block_time = *block_time.borrow_and_update();
+                    println!("Block time changed to {}", humantime::format_duration(new_block_time));
+                    interval = block_interval(new_block_time);
+                    continue;
+                }
+            };
+
+            peers.sync(config).await?;
+
+            let mut heights = vec![];
+            let height = chain.snapshot().height() + 1;
+            let paused = reply.is_none() && chain.is_paused();
+            if producers.producer_for(height) == public_key && !paused {
+                let block = chain.drain_block_from_pool(&config.block_limits).await?;
+                let sealed_block = self.store_block(block).await?;
+                println!("Sealed block {} in turn", sealed_block.block.height);
+                heights.push(sealed_block.block.height);
+                chain.publish_sealed_block(sealed_block);
+            }
+
+            if let Some(reply) = reply {
+                // requester may have given up waiting
+                let _ = reply.send(heights);
+            }
+        }
+    }
+
+    /// Seals blocks from the pool at the end of a block epoch, only an empty
+    /// block if `empty_only`, and returns the heights of the sealed blocks
+    async fn seal_epoch(
+        &self,
+        config: &Configuration,
+        epoch_end: time::Instant,
+        empty_only: bool,
+        last_block_at: &mut time::Instant,
+    ) -> Result<Vec<u32>> {
+        let chain = &config.chain;
+        let limits = &config.block_limits;
+        let heartbeat_interval = Duration::from_secs(config.heartbeat_interval as u64);
+        let mut heights = vec![];
+
+        println!("End of block epoch. Processing...");
 
+        {
+            // limit lock to smallest possible block scope
+            let pool_is_empty = chain.tx_pool.lock().await.is_empty();
+            if pool_is_empty {
+                let seal_empty = match config.empty_block_policy {
+                    EmptyBlockPolicy::Always => true,
+                    EmptyBlockPolicy::Heartbeat => {
+                        epoch_end.duration_since(*last_block_at) >= heartbeat_interval
+                    }
+                    EmptyBlockPolicy::Skip => false,
+                };
+
+                if !seal_empty {
+                    println!("No transaction in pool. Skipping...");
+                    return Ok(heights);
+                }
+
+                println!("No transaction in pool. Sealing empty block...");
+                let sealed_block = self.store_block(Block::from(vec![])).await?;
+                heights.push(sealed_block.block.height);
+                chain.publish_sealed_block(sealed_block);
+                *last_block_at = epoch_end;
+                return Ok(heights);
+            }
+        } // lock drops here
+
+        // the trigger seals the pool, not the block time
+        if empty_only {
+            return Ok(heights);
+        }
+
+        // seal blocks until the pool is drained or the epoch's block limit is reached
+        for _ in 0..limits.max_blocks_per_epoch {
+            // create new block from what fits, leftovers stay in the pool
+            let block = chain.drain_block_from_pool(limits).await?;
+            if block.transactions.is_empty() {
+                break;
+            }
+
+            // store_block
+            let sealed_block = self.store_block(block).await?;
+            heights.push(sealed_block.block.height);
+            chain.publish_sealed_block(sealed_block);
+            *last_block_at = epoch_end;
+        }
+
+        Ok(heights)
+    }
+
+    /// Consumes a [`Block`] and returns it as a [`SealedBlock`]
+    async fn store_block(&self, block: Block) -> Result<SealedBlock> {
         // retrieve latest block_data
         println!("read latest block data");
         let latest_block_data = self.persistence.read_latest_block_data().await?;
@@ -144,38 +649,66 @@ impl Node {
         // create block_data
         println!("create block data");
         let id = Uuid::now_v7().to_string();
-        let nonce = get_random_nonce(100); // uppet_limit = 100
+        let nonce = get_random_nonce(self.nonce_limit);
 
         // increment height
         let height = latest_block_data.height + 1;
         // retrieve prev block's hash
         let prev_block_hash = latest_block_data.hash;
 
-        // calculate hash
-        let hash = BlockData::get_sha256_hash(&id, &nonce, &height, &prev_block_hash);
+        let transactions: Vec<TransactionData> = block
+            .transactions
+            .into_iter()
+            .enumerate()
+            .map(|(position, t)| TransactionData::from_sealed(t, height, position as u32))
+            .collect();
+
+        // calculate hash, over the transaction hashes in block order
+        let transaction_hashes: Vec<&str> =
+            transactions.iter().map(|tx| tx.hash.as_str()).collect();
+        let hash = BlockData::get_hash(
+            self.hash_algorithm()?,
+            &id,
+            nonce,
+            height,
+            &prev_block_hash,
+            &transaction_hashes,
+        );
 
         println!("creating block data struct");
-        let block_data = BlockData {
+        let mut block_data = BlockData {
+            version: BLOCK_HEADER_VERSION,
             hash,
             id,
             nonce,
             height,
             prev_block_hash,
+            producer: None,
+            signature: None,
         };
+        if let Some(key) = &self.key {
+            key.sign(&mut block_data);
+        }
 
         println!("created block data: {:?}", block_data);
 
-        // store BlockData
+        // store BlockData with its transactions
         println!("storing block data");
-        self.persistence.store_block_data(block_data).await?;
-
-        // store transactions
-        for t in block.transactions.into_iter() {
-            let tx_data = TransactionData::try_from(t)?;
-            println!("storing transaction data");
-            self.persistence.store_transaction_data(tx_data).await?;
-        }
+        self.persistence
+            .store_sealed_block_data(&block_data, &transactions)
+            .await?;
 
-        Ok(tx.commit().await?)
+        Ok(SealedBlock {
+            block: block_data,
+            transactions,
+        })
     }
 }
+
+/// Creates the block epoch interval, an epoch that takes longer
+/// than the block time delays the next one instead of bursting to catch up
+fn block_interval(block_time: Duration) -> time::Interval {
+    let mut interval = time::interval(block_time);
+    interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    interval
+}
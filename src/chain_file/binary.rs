@@ -0,0 +1,365 @@
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, Read, Write};
+
+use super::{check_version, Header, Record, CHAIN_FILE_VERSION};
+use crate::blockchain::SealedBlock;
+use crate::encoding::LEGACY_HEADER_VERSION;
+use crate::hasher::HashAlgorithm;
+use crate::persistence::models::{BlockData, TransactionData};
+
+// first bytes of every binary chain file
+const MAGIC: &[u8; 4] = b"BFCH";
+// refuse records larger than this instead of allocating them
+const MAX_RECORD_LEN: u32 = 256 * 1024 * 1024;
+// longest hash algorithm name read from a header
+const MAX_ALGORITHM_NAME_LEN: u32 = 64;
+// first versions with block signatures, the hash algorithm
+// in the header and the block header version
+const SIGNATURES_VERSION: u16 = 2;
+const HASH_ALGORITHM_VERSION: u16 = 3;
+const HEADER_VERSION_VERSION: u16 = 4;
+
+// Layout, integers are big-endian:
+//
+//   header  MAGIC, version: u16, hash algorithm: str
+//   block   len: u32 (> 0), then `len` bytes of
+//             version: u32, id, hash: str, nonce, height: u32, prev_block_hash,
+//             producer, signature: str (empty if unsigned),
+//             transaction count: u32, then per transaction
+//               id, hash, from, to: str, instruction: bytes, fee: u32
+//   footer  len: u32 (= 0), blocks: u32
+//
+// str and bytes are a u32 length followed by the data, a transaction's
+// block height and position are implied by the block it is in
+//
+// Older versions lack fields: version 1 has no producer and signature,
+// versions before 3 no hash algorithm (SHA-256), before 4 no block
+// header version (legacy)
+
+pub fn has_magic(reader: &mut impl BufRead) -> Result<bool> {
+    Ok(reader.fill_buf()?.starts_with(MAGIC))
+}
+
+pub fn write_header(writer: &mut impl Write, hash_algorithm: HashAlgorithm) -> Result<()> {
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&CHAIN_FILE_VERSION.to_be_bytes());
+    put_bytes(&mut header, hash_algorithm.name().as_bytes());
+
+    writer.write_all(&header)?;
+    Ok(())
+}
+
+pub fn write_block(writer: &mut impl Write, sealed: &SealedBlock) -> Result<()> {
+    let block = &sealed.block;
+
+    let mut record = vec![];
+    put_u32(&mut record, block.version);
+    put_bytes(&mut record, block.id.as_bytes());
+    put_bytes(&mut record, block.hash.as_bytes());
+    put_u32(&mut record, block.nonce);
+    put_u32(&mut record, block.height);
+    put_bytes(&mut record, block.prev_block_hash.as_bytes());
+    put_bytes(
+        &mut record,
+        block.producer.as_deref().unwrap_or("").as_bytes(),
+    );
+    put_bytes(
+        &mut record,
+        block.signature.as_deref().unwrap_or("").as_bytes(),
+    );
+
+    put_u32(&mut record, sealed.transactions.len() as u32);
+    for tx in &sealed.transactions {
+        put_bytes(&mut record, tx.id.as_bytes());
+        put_bytes(&mut record, tx.hash.as_bytes());
+        put_bytes(&mut record, tx.from.as_bytes());
+        put_bytes(&mut record, tx.to.as_bytes());
+        put_bytes(&mut record, &tx.instruction);
+        put_u32(&mut record, tx.fee);
+    }
+
+    writer.write_all(&(record.len() as u32).to_be_bytes())?;
+    writer.write_all(&record)?;
+    Ok(())
+}
+
+pub fn write_footer(writer: &mut impl Write, blocks: u32) -> Result<()> {
+    writer.write_all(&0u32.to_be_bytes())?;
+    writer.write_all(&blocks.to_be_bytes())?;
+    Ok(())
+}
+
+pub(super) fn read_header(reader: &mut impl BufRead) -> Result<Header> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    let mut version = [0; 2];
+    reader.read_exact(&mut version)?;
+    let version = u16::from_be_bytes(version);
+    check_version(version)?;
+
+    if version < HASH_ALGORITHM_VERSION {
+        return Ok(Header {
+            version,
+            hash_algorithm: HashAlgorithm::Sha256,
+        });
+    }
+
+    let len = read_u32(reader).context("chain file header is truncated")?;
+    if len > MAX_ALGORITHM_NAME_LEN {
+        bail!("chain file header names a hash algorithm of {len} bytes");
+    }
+    let mut name = vec![0; len as usize];
+    reader
+        .read_exact(&mut name)
+        .context("chain file header is truncated")?;
+
+    Ok(Header {
+        version,
+        hash_algorithm: String::from_utf8_lossy(&name).parse()?,
+    })
+}
+
+pub(super) fn read_record(reader: &mut impl BufRead, version: u16) -> Result<Record> {
+    let Ok(len) = read_u32(reader) else {
+        bail!("chain file ends before its footer");
+    };
+
+    if len == 0 {
+        let blocks = read_u32(reader).context("chain file footer is truncated")?;
+        return Ok(Record::Footer { blocks });
+    }
+    if len > MAX_RECORD_LEN {
+        bail!("chain file record of {len} bytes is too large");
+    }
+
+    let mut record = vec![0; len as usize];
+    reader
+        .read_exact(&mut record)
+        .context("chain file ends in the middle of a block")?;
+
+    let mut decoder = Decoder { bytes: &record };
+    let header_version = if version >= HEADER_VERSION_VERSION {
+        decoder.u32()?
+    } else {
+        LEGACY_HEADER_VERSION
+    };
+    let mut block = BlockData {
+        version: header_version,
+        id: decoder.string()?,
+        hash: decoder.string()?,
+        nonce: decoder.u32()?,
+        height: decoder.u32()?,
+        prev_block_hash: decoder.string()?,
+        producer: None,
+        signature: None,
+    };
+    if version >= SIGNATURES_VERSION {
+        block.producer = decoder.optional_string()?;
+        block.signature = decoder.optional_string()?;
+    }
+
+    let count = decoder.u32()?;
+    let mut transactions = vec![];
+    for position in 0..count {
+        transactions.push(TransactionData {
+            id: decoder.string()?,
+            hash: decoder.string()?,
+            from: decoder.string()?,
+            to: decoder.string()?,
+            instruction: decoder.bytes()?.to_vec(),
+            fee: decoder.u32()?,
+            block_height: block.height,
+            position,
+        });
+    }
+
+    if !decoder.bytes.is_empty() {
+        bail!("block {} record has trailing bytes", block.height);
+    }
+
+    Ok(Record::Block(SealedBlock {
+        block,
+        transactions,
+    }))
+}
+
+fn put_u32(record: &mut Vec<u8>, value: u32) {
+    record.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_bytes(record: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(record, bytes.len() as u32);
+    record.extend_from_slice(bytes);
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+// reads fields off the front of a block record
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            bail!("chain file record is truncated");
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        Ok(String::from_utf8(self.bytes()?.to_vec())?)
+    }
+
+    // empty is none
+    fn optional_string(&mut self) -> Result<Option<String>> {
+        Ok(Some(self.string()?).filter(|value| !value.is_empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_file::tests::{
+        assert_same_blocks, export, import, import_error, sample_chain,
+    };
+    use crate::chain_file::ChainFormat;
+
+    // a file as written by an older version, leaving out what it lacked
+    fn file_of_version(version: u16, blocks: &[SealedBlock]) -> Vec<u8> {
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&version.to_be_bytes());
+        if version >= HASH_ALGORITHM_VERSION {
+            put_bytes(&mut file, b"blake3");
+        }
+
+        for sealed in blocks {
+            let block = &sealed.block;
+            let mut record = vec![];
+            if version >= HEADER_VERSION_VERSION {
+                put_u32(&mut record, block.version);
+            }
+            put_bytes(&mut record, block.id.as_bytes());
+            put_bytes(&mut record, block.hash.as_bytes());
+            put_u32(&mut record, block.nonce);
+            put_u32(&mut record, block.height);
+            put_bytes(&mut record, block.prev_block_hash.as_bytes());
+            if version >= SIGNATURES_VERSION {
+                put_bytes(
+                    &mut record,
+                    block.producer.as_deref().unwrap_or("").as_bytes(),
+                );
+                put_bytes(
+                    &mut record,
+                    block.signature.as_deref().unwrap_or("").as_bytes(),
+                );
+            }
+            put_u32(&mut record, sealed.transactions.len() as u32);
+            for tx in &sealed.transactions {
+                put_bytes(&mut record, tx.id.as_bytes());
+                put_bytes(&mut record, tx.hash.as_bytes());
+                put_bytes(&mut record, tx.from.as_bytes());
+                put_bytes(&mut record, tx.to.as_bytes());
+                put_bytes(&mut record, &tx.instruction);
+                put_u32(&mut record, tx.fee);
+            }
+            put_bytes(&mut file, &record);
+        }
+
+        write_footer(&mut file, blocks.len() as u32).unwrap();
+        file
+    }
+
+    #[test]
+    fn round_trips_a_chain() {
+        let blocks = sample_chain();
+
+        let (format, hash_algorithm, read) = import(&export(ChainFormat::Binary, &blocks)).unwrap();
+
+        assert!(matches!(format, ChainFormat::Binary));
+        assert_eq!(hash_algorithm, HashAlgorithm::Blake3);
+        assert_same_blocks(&read, &blocks);
+    }
+
+    #[test]
+    fn reads_files_of_older_versions() {
+        for version in 1..CHAIN_FILE_VERSION {
+            let mut expected = sample_chain();
+            for sealed in &mut expected {
+                sealed.block.version = LEGACY_HEADER_VERSION;
+                if version < SIGNATURES_VERSION {
+                    sealed.block.producer = None;
+                    sealed.block.signature = None;
+                }
+            }
+
+            let (_, hash_algorithm, read) = import(&file_of_version(version, &expected)).unwrap();
+
+            let expected_algorithm = if version < HASH_ALGORITHM_VERSION {
+                HashAlgorithm::Sha256
+            } else {
+                HashAlgorithm::Blake3
+            };
+            assert_eq!(hash_algorithm, expected_algorithm, "version {version}");
+            assert_same_blocks(&read, &expected);
+        }
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        let file = file_of_version(CHAIN_FILE_VERSION + 1, &sample_chain());
+
+        let expected = format!("unsupported chain file version {}", CHAIN_FILE_VERSION + 1);
+        assert!(import_error(&file).contains(&expected));
+    }
+
+    #[test]
+    fn refuses_truncated_files() {
+        let file = export(ChainFormat::Binary, &sample_chain());
+
+        // without the footer, in the middle of a block, in the middle of the header
+        for len in [file.len() - 8, file.len() - 20, 8] {
+            assert!(import(&file[..len]).is_err(), "truncated to {len} bytes");
+        }
+        assert!(import_error(&file[..file.len() - 8]).contains("ends before its footer"));
+    }
+
+    #[test]
+    fn refuses_corrupt_files() {
+        let file = export(ChainFormat::Binary, &sample_chain());
+
+        // the footer counts one block too many
+        let mut miscounted = file.clone();
+        let last = miscounted.len() - 1;
+        miscounted[last] += 1;
+        assert!(import_error(&miscounted).contains("footer counts 3 blocks"));
+
+        // the first record claims to be larger than any block
+        let header_len = MAGIC.len() + 2 + 4 + "blake3".len();
+        let mut oversized = file.clone();
+        oversized[header_len..header_len + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(import_error(&oversized).contains("too large"));
+
+        // a field runs past the end of its record
+        let mut overrun = file;
+        let id_len = header_len + 4 + 4;
+        overrun[id_len..id_len + 4].copy_from_slice(&1000u32.to_be_bytes());
+        assert!(import_error(&overrun).contains("truncated"));
+    }
+}
@@ -0,0 +1,260 @@
+mod binary;
+mod ndjson;
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use crate::blockchain::SealedBlock;
+use crate::hasher::HashAlgorithm;
+
+/// Version written in the header of every chain file,
+/// files of any version up to it are read, newer ones are refused
+pub const CHAIN_FILE_VERSION: u16 = 4;
+
+/// Encoding of an exported chain
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ChainFormat {
+    /// One JSON record per line
+    Ndjson,
+    /// Length-prefixed binary records
+    Binary,
+}
+
+impl fmt::Display for ChainFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainFormat::Ndjson => write!(f, "NDJSON"),
+            ChainFormat::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+/// Streams [`SealedBlock`]s into a chain file, between a header and a footer
+pub struct ChainWriter<W: Write> {
+    format: ChainFormat,
+    writer: W,
+    blocks: u32,
+}
+
+impl<W: Write> ChainWriter<W> {
+    /// Writes the header, naming the `hash_algorithm` of the chain
+    pub fn new(format: ChainFormat, mut writer: W, hash_algorithm: HashAlgorithm) -> Result<Self> {
+        match format {
+            ChainFormat::Ndjson => ndjson::write_header(&mut writer, hash_algorithm)?,
+            ChainFormat::Binary => binary::write_header(&mut writer, hash_algorithm)?,
+        }
+
+        Ok(Self {
+            format,
+            writer,
+            blocks: 0,
+        })
+    }
+
+    pub fn write_block(&mut self, sealed: &SealedBlock) -> Result<()> {
+        match self.format {
+            ChainFormat::Ndjson => ndjson::write_block(&mut self.writer, sealed)?,
+            ChainFormat::Binary => binary::write_block(&mut self.writer, sealed)?,
+        }
+        self.blocks += 1;
+
+        Ok(())
+    }
+
+    /// Writes the footer, returns the amount of blocks written
+    pub fn finish(mut self) -> Result<u32> {
+        match self.format {
+            ChainFormat::Ndjson => ndjson::write_footer(&mut self.writer, self.blocks)?,
+            ChainFormat::Binary => binary::write_footer(&mut self.writer, self.blocks)?,
+        }
+        self.writer.flush()?;
+
+        Ok(self.blocks)
+    }
+}
+
+/// Streams [`SealedBlock`]s out of a chain file of either format
+pub struct ChainReader<R: BufRead> {
+    format: ChainFormat,
+    header: Header,
+    reader: R,
+    blocks: u32,
+    finished: bool,
+}
+
+impl<R: BufRead> ChainReader<R> {
+    /// Detects the format and checks the header
+    pub fn new(mut reader: R) -> Result<Self> {
+        let (format, header) = if binary::has_magic(&mut reader)? {
+            (ChainFormat::Binary, binary::read_header(&mut reader)?)
+        } else {
+            (ChainFormat::Ndjson, ndjson::read_header(&mut reader)?)
+        };
+
+        Ok(Self {
+            format,
+            header,
+            reader,
+            blocks: 0,
+            finished: false,
+        })
+    }
+
+    pub fn format(&self) -> ChainFormat {
+        self.format
+    }
+
+    /// Hash algorithm of the exported chain, named in the header
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.header.hash_algorithm
+    }
+
+    /// Returns the next [`SealedBlock`], `None` once the footer is read,
+    /// a file that ends before its footer is an error
+    pub fn next_block(&mut self) -> Result<Option<SealedBlock>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let record = match self.format {
+            ChainFormat::Ndjson => ndjson::read_record(&mut self.reader)?,
+            ChainFormat::Binary => binary::read_record(&mut self.reader, self.header.version)?,
+        };
+
+        match record {
+            Record::Block(sealed) => {
+                self.blocks += 1;
+                Ok(Some(sealed))
+            }
+            Record::Footer { blocks } => {
+                if blocks != self.blocks {
+                    bail!(
+                        "chain file footer counts {blocks} blocks, but {} were read",
+                        self.blocks
+                    );
+                }
+                self.finished = true;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// What the header of a chain file says
+struct Header {
+    version: u16,
+    // files before version 3 don't name it, they're SHA-256
+    hash_algorithm: HashAlgorithm,
+}
+
+/// What follows the header of a chain file
+enum Record {
+    Block(SealedBlock),
+    Footer { blocks: u32 },
+}
+
+fn check_version(version: u16) -> Result<()> {
+    if !(1..=CHAIN_FILE_VERSION).contains(&version) {
+        bail!("unsupported chain file version {version}, expected 1 to {CHAIN_FILE_VERSION}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::BLOCK_HEADER_VERSION;
+    use crate::persistence::models::{BlockData, TransactionData};
+
+    // hashes aren't checked when reading, any text will do
+    pub(super) fn sample_chain() -> Vec<SealedBlock> {
+        let genesis = BlockData {
+            version: BLOCK_HEADER_VERSION,
+            id: String::from("0192a0c4-8e00-7000-8000-000000000001"),
+            hash: String::from("aa01"),
+            nonce: 7,
+            height: 1,
+            prev_block_hash: String::new(),
+            producer: None,
+            signature: None,
+        };
+        let block = BlockData {
+            version: BLOCK_HEADER_VERSION,
+            id: String::from("0192a0c4-8e00-7000-8000-000000000002"),
+            hash: String::from("aa02"),
+            nonce: 12,
+            height: 2,
+            prev_block_hash: String::from("aa01"),
+            producer: Some(String::from("producer-key")),
+            signature: Some(String::from("signature")),
+        };
+        let transactions = vec![
+            TransactionData {
+                id: String::from("0192a0c4-8e00-7000-8000-000000000003"),
+                hash: String::from("bb01"),
+                from: String::from("alice"),
+                to: String::from("bob"),
+                instruction: b"hi".to_vec(),
+                fee: 3,
+                block_height: 2,
+                position: 0,
+            },
+            TransactionData {
+                id: String::from("0192a0c4-8e00-7000-8000-000000000004"),
+                hash: String::from("bb02"),
+                from: String::from("bob"),
+                to: String::from("carol"),
+                // not UTF-8
+                instruction: vec![0xff, 0x00],
+                fee: 0,
+                block_height: 2,
+                position: 1,
+            },
+        ];
+
+        vec![
+            SealedBlock {
+                block: genesis,
+                transactions: vec![],
+            },
+            SealedBlock {
+                block,
+                transactions,
+            },
+        ]
+    }
+
+    pub(super) fn export(format: ChainFormat, blocks: &[SealedBlock]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut writer = ChainWriter::new(format, &mut bytes, HashAlgorithm::Blake3).unwrap();
+        for sealed in blocks {
+            writer.write_block(sealed).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), blocks.len() as u32);
+        bytes
+    }
+
+    /// Reads every block of `bytes`, along with the format and hash algorithm
+    pub(super) fn import(bytes: &[u8]) -> Result<(ChainFormat, HashAlgorithm, Vec<SealedBlock>)> {
+        let mut reader = ChainReader::new(bytes)?;
+        let mut blocks = vec![];
+        while let Some(sealed) = reader.next_block()? {
+            blocks.push(sealed);
+        }
+        Ok((reader.format(), reader.hash_algorithm(), blocks))
+    }
+
+    pub(super) fn import_error(bytes: &[u8]) -> String {
+        format!("{:#}", import(bytes).expect_err("import should fail"))
+    }
+
+    // compared as JSON, blocks don't implement PartialEq
+    pub(super) fn assert_same_blocks(read: &[SealedBlock], written: &[SealedBlock]) {
+        assert_eq!(
+            serde_json::to_value(read).unwrap(),
+            serde_json::to_value(written).unwrap()
+        );
+    }
+}
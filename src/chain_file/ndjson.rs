@@ -0,0 +1,239 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+use super::{check_version, Header, Record, CHAIN_FILE_VERSION};
+use crate::blockchain::SealedBlock;
+use crate::hasher::HashAlgorithm;
+
+// tells chain files apart from other NDJSON
+const FORMAT_NAME: &str = "block-factory-chain";
+
+/// One line of an NDJSON chain file, as written
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum LineOut<'a> {
+    Header {
+        format: &'a str,
+        version: u16,
+        hash_algorithm: HashAlgorithm,
+    },
+    Block(&'a SealedBlock),
+    Footer {
+        blocks: u32,
+    },
+}
+
+/// One line of an NDJSON chain file, as read
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum LineIn {
+    Header {
+        format: String,
+        version: u16,
+        // missing before version 3
+        #[serde(default)]
+        hash_algorithm: Option<HashAlgorithm>,
+    },
+    Block(SealedBlock),
+    Footer {
+        blocks: u32,
+    },
+}
+
+pub fn write_header(writer: &mut impl Write, hash_algorithm: HashAlgorithm) -> Result<()> {
+    write_line(
+        writer,
+        &LineOut::Header {
+            format: FORMAT_NAME,
+            version: CHAIN_FILE_VERSION,
+            hash_algorithm,
+        },
+    )
+}
+
+pub fn write_block(writer: &mut impl Write, sealed: &SealedBlock) -> Result<()> {
+    write_line(writer, &LineOut::Block(sealed))
+}
+
+pub fn write_footer(writer: &mut impl Write, blocks: u32) -> Result<()> {
+    write_line(writer, &LineOut::Footer { blocks })
+}
+
+pub(super) fn read_header(reader: &mut impl BufRead) -> Result<Header> {
+    let Some(line) = read_line(reader)? else {
+        bail!("empty chain file");
+    };
+
+    match serde_json::from_str::<LineIn>(&line) {
+        Ok(LineIn::Header {
+            format,
+            version,
+            hash_algorithm,
+        }) if format == FORMAT_NAME => {
+            check_version(version)?;
+            Ok(Header {
+                version,
+                hash_algorithm: hash_algorithm.unwrap_or_default(),
+            })
+        }
+        _ => bail!("not a chain file, the first line must be a {FORMAT_NAME} header"),
+    }
+}
+
+pub(super) fn read_record(reader: &mut impl BufRead) -> Result<Record> {
+    let Some(line) = read_line(reader)? else {
+        bail!("chain file ends before its footer");
+    };
+
+    match serde_json::from_str::<LineIn>(&line).context("invalid chain file line")? {
+        LineIn::Block(sealed) => Ok(Record::Block(sealed)),
+        LineIn::Footer { blocks } => Ok(Record::Footer { blocks }),
+        LineIn::Header { .. } => bail!("unexpected header in the middle of the chain file"),
+    }
+}
+
+fn write_line(writer: &mut impl Write, line: &LineOut) -> Result<()> {
+    serde_json::to_writer(&mut *writer, line)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+// next non-empty line, `None` at the end of the file
+fn read_line(reader: &mut impl BufRead) -> Result<Option<String>> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.trim().is_empty() {
+            return Ok(Some(line));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_file::tests::{
+        assert_same_blocks, export, import, import_error, sample_chain,
+    };
+    use crate::chain_file::ChainFormat;
+    use crate::encoding::LEGACY_HEADER_VERSION;
+    use serde_json::{json, Value};
+
+    // a file as written by an older version, leaving out what it lacked
+    fn file_of_version(version: u16, blocks: &[SealedBlock]) -> Vec<u8> {
+        let mut header = json!({ "type": "header", "format": FORMAT_NAME, "version": version });
+        if version >= 3 {
+            header["hash_algorithm"] = json!("blake3");
+        }
+
+        let mut lines = vec![header];
+        for sealed in blocks {
+            let mut line = serde_json::to_value(sealed).unwrap();
+            line["type"] = json!("block");
+            let block = line["block"].as_object_mut().unwrap();
+            if version < 4 {
+                block.remove("version");
+            }
+            if version < 2 {
+                block.remove("producer");
+                block.remove("signature");
+            }
+            lines.push(line);
+        }
+        lines.push(json!({ "type": "footer", "blocks": blocks.len() }));
+
+        lines
+            .iter()
+            .map(|line| format!("{line}\n"))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn round_trips_a_chain() {
+        let blocks = sample_chain();
+
+        let (format, hash_algorithm, read) = import(&export(ChainFormat::Ndjson, &blocks)).unwrap();
+
+        assert!(matches!(format, ChainFormat::Ndjson));
+        assert_eq!(hash_algorithm, HashAlgorithm::Blake3);
+        assert_same_blocks(&read, &blocks);
+    }
+
+    #[test]
+    fn reads_files_of_older_versions() {
+        for version in 1..CHAIN_FILE_VERSION {
+            let mut expected = sample_chain();
+            for sealed in &mut expected {
+                sealed.block.version = LEGACY_HEADER_VERSION;
+                if version < 2 {
+                    sealed.block.producer = None;
+                    sealed.block.signature = None;
+                }
+            }
+
+            let (_, hash_algorithm, read) = import(&file_of_version(version, &expected)).unwrap();
+
+            let expected_algorithm = if version < 3 {
+                HashAlgorithm::Sha256
+            } else {
+                HashAlgorithm::Blake3
+            };
+            assert_eq!(hash_algorithm, expected_algorithm, "version {version}");
+            assert_same_blocks(&read, &expected);
+        }
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        let file = file_of_version(CHAIN_FILE_VERSION + 1, &sample_chain());
+
+        let expected = format!("unsupported chain file version {}", CHAIN_FILE_VERSION + 1);
+        assert!(import_error(&file).contains(&expected));
+    }
+
+    #[test]
+    fn refuses_truncated_files() {
+        let file = String::from_utf8(export(ChainFormat::Ndjson, &sample_chain())).unwrap();
+        let lines: Vec<&str> = file.lines().collect();
+
+        // without the footer
+        let without_footer = lines[..lines.len() - 1].join("\n");
+        assert!(import_error(without_footer.as_bytes()).contains("ends before its footer"));
+
+        // cut off in the middle of a block
+        let cut = &file[..file.len() - lines[lines.len() - 1].len() - 10];
+        assert!(import_error(cut.as_bytes()).contains("invalid chain file line"));
+
+        assert!(import_error(b"").contains("empty chain file"));
+    }
+
+    #[test]
+    fn refuses_corrupt_files() {
+        let file = String::from_utf8(export(ChainFormat::Ndjson, &sample_chain())).unwrap();
+
+        let miscounted = file.replace(
+            r#"{"type":"footer","blocks":2}"#,
+            r#"{"type":"footer","blocks":3}"#,
+        );
+        assert!(import_error(miscounted.as_bytes()).contains("footer counts 3 blocks"));
+
+        let mut lines: Vec<Value> = file
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        lines[1]["block"]["height"] = json!("one");
+        let mistyped: String = lines.iter().map(|line| format!("{line}\n")).collect();
+        assert!(import_error(mistyped.as_bytes()).contains("invalid chain file line"));
+
+        let header = file.lines().next().unwrap();
+        let repeated_header = file.replacen(header, &format!("{header}\n{header}"), 1);
+        assert!(import_error(repeated_header.as_bytes()).contains("unexpected header"));
+
+        assert!(import_error(b"{\"type\":\"footer\",\"blocks\":0}\n").contains("not a chain file"));
+    }
+}
@@ -1,8 +1,12 @@
-use super::config::Mode;
+use super::chain_file::ChainFormat;
+use super::config::{BlockTrigger, EmptyBlockPolicy, Mode};
+use super::hasher::HashAlgorithm;
+use super::utils::parse_block_time;
 use clap::{
-    builder::{EnumValueParser, RangedU64ValueParser},
-    Arg, ArgMatches, Command,
+    builder::{EnumValueParser, PathBufValueParser, RangedU64ValueParser},
+    value_parser, Arg, ArgMatches, Command,
 };
+use std::net::IpAddr;
 
 pub struct Cli {}
 
@@ -11,30 +15,210 @@ impl Cli {
         Command::new("block-factory")
             .version("0.1.0")
             .about("Generate blocks from incoming transactions")
+            // no subcommand serves, as before subcommands existed
             .arg(
-                Arg::new("PORT")
-                    .help("Port to use in serving the factory")
-                    .long("port")
-                    .short('p')
-                    .value_parser(RangedU64ValueParser::<u32>::new().range(1..))
-                    .default_value("8080"),
+                Arg::new("CONFIG")
+                    .help("TOML configuration file, overridden by environment variables and flags")
+                    .long("config")
+                    .short('c')
+                    .global(true)
+                    .value_parser(PathBufValueParser::new()),
             )
             .arg(
-                Arg::new("BLOCKTIME")
-                    .help("Amount of seconds to wait before creating a block")
-                    .long("block-time")
-                    .short('b')
-                    .value_parser(RangedU64ValueParser::<u32>::new().range(1..))
-                    .default_value("1"),
+                Arg::new("DATABASEURL")
+                    .help("SQLite database URL (e.g. sqlite:db/blocks.db)")
+                    .long("database-url")
+                    .global(true),
             )
             .arg(
-                Arg::new("MODE")
-                    .help("Mode for block factory")
-                    .long("mode")
-                    .short('m')
-                    .value_parser(EnumValueParser::<Mode>::new())
-                    .default_value("full"),
+                Arg::new("BACKUPDIR")
+                    .help("Directory backups are written to")
+                    .long("backup-dir")
+                    .global(true)
+                    .value_parser(PathBufValueParser::new()),
+            )
+            .args(serve_args())
+            .subcommand(
+                Command::new("serve")
+                    .about("Run the block factory and serve the API and explorer (default)")
+                    .args(serve_args()),
+            )
+            .subcommand(
+                Command::new("verify")
+                    .about("Check every block hash, transaction hash and the chain linkage")
+                    .arg(producers_arg()),
+            )
+            .subcommand(
+                Command::new("export")
+                    .about("Write the chain with its transactions to a versioned chain file")
+                    .arg(
+                        Arg::new("FORMAT")
+                            .help("Encoding of the chain file")
+                            .long("format")
+                            .short('f')
+                            .value_parser(EnumValueParser::<ChainFormat>::new())
+                            .default_value("ndjson"),
+                    )
+                    .arg(
+                        Arg::new("OUTPUT")
+                            .help("File to write to (stdout if not set)")
+                            .long("output")
+                            .short('o')
+                            .value_parser(PathBufValueParser::new()),
+                    ),
+            )
+            .subcommand(
+                Command::new("import")
+                    .about(
+                        "Load a chain file (either format) into an empty database, checking it block by block",
+                    )
+                    .arg(
+                        Arg::new("INPUT")
+                            .help("File to read from (- for stdin)")
+                            .required(true)
+                            .value_parser(PathBufValueParser::new()),
+                    )
+                    .arg(producers_arg())
+                    .arg(hash_algorithm_arg()),
+            )
+            .subcommand(
+                Command::new("backup")
+                    .about("Take a consistent backup of the database, even while it is served"),
+            )
+            .subcommand(
+                Command::new("restore")
+                    .about("Check a backup against its manifest and chain, then copy it to the database path")
+                    .arg(
+                        Arg::new("BACKUP")
+                            .help("Backup file (its manifest is read from <BACKUP>.json)")
+                            .required(true)
+                            .value_parser(PathBufValueParser::new()),
+                    ),
+            )
+            .subcommand(
+                Command::new("inspect")
+                    .about("Print a stored block or transaction as JSON")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("block")
+                            .about("Print a block with its transactions")
+                            .arg(
+                                Arg::new("BLOCK")
+                                    .help("Block height or hash")
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("tx")
+                            .about("Print a sealed transaction")
+                            .arg(Arg::new("HASH").help("Transaction hash").required(true)),
+                    ),
+            )
+            .subcommand(
+                Command::new("stats")
+                    .about("Print block, transaction, fee and address totals as JSON"),
+            )
+            .subcommand(
+                Command::new("keygen")
+                    .about("Create a key to sign blocks with and print its public key")
+                    .arg(
+                        Arg::new("OUTPUT")
+                            .help("Key file to create, must not exist yet")
+                            .required(true)
+                            .value_parser(PathBufValueParser::new()),
+                    ),
             )
             .get_matches()
     }
 }
+
+// flags of `serve`, also accepted without a subcommand
+fn serve_args() -> Vec<Arg> {
+    vec![
+        Arg::new("BINDADDRESS")
+            .help("Address to bind the server to")
+            .long("bind-address")
+            .value_parser(value_parser!(IpAddr)),
+        Arg::new("PORT")
+            .help("Port to use in serving the factory")
+            .long("port")
+            .short('p')
+            .value_parser(RangedU64ValueParser::<u16>::new().range(1..=u16::MAX as u64)),
+        Arg::new("BLOCKTIME")
+            .help("Time to wait before creating a block (e.g. 250ms, 2s, or whole seconds)")
+            .long("block-time")
+            .short('b')
+            .value_parser(parse_block_time),
+        Arg::new("MAXBLOCKTXS")
+            .help("Maximum amount of transactions per block (unlimited if not set)")
+            .long("max-block-txs")
+            .value_parser(RangedU64ValueParser::<usize>::new().range(1..)),
+        Arg::new("MAXBLOCKBYTES")
+            .help("Maximum total transaction bytes per block (unlimited if not set)")
+            .long("max-block-bytes")
+            .value_parser(RangedU64ValueParser::<usize>::new().range(1..)),
+        Arg::new("BLOCKSPEREPOCH")
+            .help("Maximum amount of blocks to seal per block epoch")
+            .long("blocks-per-epoch")
+            .value_parser(RangedU64ValueParser::<u32>::new().range(1..)),
+        Arg::new("EMPTYBLOCKS")
+            .help("Policy for sealing blocks when the pool is empty")
+            .long("empty-blocks")
+            .value_parser(EnumValueParser::<EmptyBlockPolicy>::new()),
+        Arg::new("HEARTBEAT")
+            .help("Amount of seconds without a block before a heartbeat block is sealed")
+            .long("heartbeat-interval")
+            .value_parser(RangedU64ValueParser::<u32>::new().range(1..)),
+        Arg::new("TRIGGER")
+            .help("What makes the factory seal a block")
+            .long("trigger")
+            .short('t')
+            .value_parser(EnumValueParser::<BlockTrigger>::new()),
+        Arg::new("SEALTHRESHOLD")
+            .help("Amount of pooled transactions that triggers a seal (size and hybrid triggers)")
+            .long("seal-threshold")
+            .value_parser(RangedU64ValueParser::<usize>::new().range(1..)),
+        Arg::new("NONCELIMIT")
+            .help("Exclusive upper limit of block nonces")
+            .long("nonce-limit")
+            .value_parser(RangedU64ValueParser::<u32>::new().range(1..)),
+        hash_algorithm_arg(),
+        Arg::new("ASSETSDIR")
+            .help("Serve assets from this directory before the embedded ones (for development)")
+            .long("assets-dir")
+            .value_parser(PathBufValueParser::new()),
+        Arg::new("MODE")
+            .help("Mode for block factory")
+            .long("mode")
+            .short('m')
+            .value_parser(EnumValueParser::<Mode>::new()),
+        Arg::new("PRIMARYURL")
+            .help("Replicate blocks from the API of this factory (query-only mode)")
+            .long("primary-url"),
+        producers_arg(),
+        Arg::new("PRODUCERKEY")
+            .help("Key file to sign sealed blocks with (see `keygen`)")
+            .long("producer-key")
+            .value_parser(PathBufValueParser::new()),
+        Arg::new("PEERS")
+            .help("APIs of the other producers to pull blocks from, comma separated")
+            .long("peers")
+            .value_delimiter(','),
+    ]
+}
+
+// serving, verifying and importing all check the producer rotation
+fn producers_arg() -> Arg {
+    Arg::new("PRODUCERS")
+        .help("Public keys taking turns sealing blocks, comma separated (proof-of-authority)")
+        .long("producers")
+        .value_delimiter(',')
+}
+
+// serving and importing may start a new chain
+fn hash_algorithm_arg() -> Arg {
+    Arg::new("HASHALGORITHM")
+        .help("Hash algorithm of a new chain, existing chains keep theirs")
+        .long("hash-algorithm")
+        .value_parser(EnumValueParser::<HashAlgorithm>::new())
+}
@@ -0,0 +1,22 @@
+use anyhow::Result;
+
+use crate::backup;
+use crate::config::Settings;
+use crate::persistence::sqlite::SqlitePersistence;
+
+/// Backs up the database into the backup directory
+pub async fn run(settings: &Settings) -> Result<()> {
+    // the node may be running on it, VACUUM INTO only reads
+    let persistence = SqlitePersistence::connect_read_only(&settings.database_url).await?;
+
+    let manifest = backup::create(&persistence, &settings.backup_dir).await?;
+
+    println!(
+        "Backed up chain at height {} ({}) to {}",
+        manifest.height,
+        manifest.hash,
+        settings.backup_dir.join(&manifest.file).display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::chain_file::{ChainFormat, ChainWriter};
+use crate::config::Settings;
+use crate::hasher::chain_hash_algorithm;
+use crate::persistence::{sqlite::SqlitePersistence, walker::ChainWalker};
+
+/// Writes the stored chain in `format` to `output` (stdout if `None`)
+pub async fn run(settings: &Settings, format: ChainFormat, output: Option<&PathBuf>) -> Result<()> {
+    let persistence = SqlitePersistence::connect_read_only(&settings.database_url).await?;
+
+    let writer: BufWriter<Box<dyn Write>> = match output {
+        Some(path) => BufWriter::new(Box::new(File::create(path)?)),
+        None => BufWriter::new(Box::new(io::stdout().lock())),
+    };
+
+    let hash_algorithm = chain_hash_algorithm(&persistence).await?;
+    let mut chain_writer = ChainWriter::new(format, writer, hash_algorithm)?;
+    let mut walker = ChainWalker::new(&persistence);
+    while let Some(sealed) = walker.next().await? {
+        chain_writer.write_block(&sealed)?;
+    }
+    let blocks = chain_writer.finish()?;
+
+    // stdout may be the export itself
+    eprintln!("Exported {blocks} blocks");
+
+    Ok(())
+}
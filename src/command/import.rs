@@ -0,0 +1,79 @@
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use crate::chain_file::ChainReader;
+use crate::config::Settings;
+use crate::hasher::init_hash_algorithm;
+use crate::persistence::{sqlite::SqlitePersistence, NodePersistency};
+use crate::verifier::ChainVerifier;
+
+/// Loads an export of either format from `input` (`-` for stdin)
+/// into an empty database, each block is checked before it is stored
+pub async fn run(settings: &Settings, input: &Path) -> Result<()> {
+    let persistence = SqlitePersistence::connect(&settings.database_url).await?;
+
+    // never mix an imported chain with an existing one
+    if persistence.read_block_data_by_height(1).await?.is_some() {
+        bail!("database already holds a chain, import needs an empty database");
+    }
+
+    let reader: Box<dyn BufRead> = if input == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(input)?))
+    };
+
+    let mut chain_reader = ChainReader::new(reader)?;
+    println!("Importing {} chain file...", chain_reader.format());
+
+    // the imported chain keeps the algorithm it was hashed with
+    let hash_algorithm = chain_reader.hash_algorithm();
+    if let Some(configured) = settings.hash_algorithm {
+        if configured != hash_algorithm {
+            bail!("chain file hashes with {hash_algorithm}, not the configured {configured}");
+        }
+    }
+    init_hash_algorithm(&persistence, Some(hash_algorithm)).await?;
+
+    let mut verifier = ChainVerifier::new(hash_algorithm).with_producers(settings.producers()?);
+    if let Err(err) = load(&persistence, &mut chain_reader, &mut verifier).await {
+        // blocks before the failure are already stored
+        let stored = verifier.tip().map_or(0, |tip| tip.height);
+        return Err(err.context(format!(
+            "import stopped after block {stored}, remove the database before retrying"
+        )));
+    }
+
+    let Some(tip) = verifier.tip() else {
+        bail!("no blocks to import");
+    };
+
+    println!(
+        "Imported {} blocks and {} transactions, tip at height {} ({})",
+        verifier.blocks(),
+        verifier.transactions(),
+        tip.height,
+        tip.hash
+    );
+
+    Ok(())
+}
+
+// checks then stores each block until the footer
+async fn load(
+    persistence: &SqlitePersistence,
+    chain_reader: &mut ChainReader<impl BufRead>,
+    verifier: &mut ChainVerifier,
+) -> Result<()> {
+    while let Some(sealed) = chain_reader.next_block()? {
+        verifier.check(&sealed.block, &sealed.transactions)?;
+
+        persistence
+            .store_sealed_block_data(&sealed.block, &sealed.transactions)
+            .await?;
+    }
+
+    Ok(())
+}
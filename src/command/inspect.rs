@@ -0,0 +1,42 @@
+use anyhow::{bail, Result};
+
+use crate::blockchain::SealedBlock;
+use crate::config::Settings;
+use crate::persistence::{sqlite::SqlitePersistence, NodePersistency};
+
+/// Prints the block at a height or with a hash, with its transactions
+pub async fn block(settings: &Settings, block: &str) -> Result<()> {
+    let persistence = SqlitePersistence::connect_read_only(&settings.database_url).await?;
+
+    let block_data = match block.parse::<u32>() {
+        Ok(height) => persistence.read_block_data_by_height(height).await?,
+        Err(_) => persistence.read_block_data_by_hash(block).await?,
+    };
+    let Some(block_data) = block_data else {
+        bail!("no block at height or with hash {block}");
+    };
+
+    let transactions = persistence
+        .read_block_transaction_data(block_data.height)
+        .await?;
+
+    let sealed = SealedBlock {
+        block: block_data,
+        transactions,
+    };
+    println!("{}", serde_json::to_string_pretty(&sealed)?);
+
+    Ok(())
+}
+
+/// Prints the sealed transaction with `hash`
+pub async fn transaction(settings: &Settings, hash: &str) -> Result<()> {
+    let persistence = SqlitePersistence::connect_read_only(&settings.database_url).await?;
+
+    let Some(tx) = persistence.read_transaction_data_by_hash(hash).await? else {
+        bail!("no sealed transaction with hash {hash}");
+    };
+    println!("{}", serde_json::to_string_pretty(&tx)?);
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::keys::NodeKey;
+
+/// Creates a block signing key at `output`, the public key goes in `producers`
+pub fn run(output: &Path) -> Result<()> {
+    let key = NodeKey::generate(output)?;
+
+    eprintln!("Wrote key to {}", output.display());
+    println!("{}", key.public_key());
+
+    Ok(())
+}
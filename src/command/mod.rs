@@ -0,0 +1,9 @@
+pub mod backup;
+pub mod export;
+pub mod import;
+pub mod inspect;
+pub mod keygen;
+pub mod restore;
+pub mod serve;
+pub mod stats;
+pub mod verify;
@@ -0,0 +1,50 @@
+use anyhow::{bail, Result};
+use sqlx::sqlite::SqliteConnectOptions;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::backup;
+use crate::config::Settings;
+
+/// Checks `backup_path` then copies it to where the database is configured
+pub async fn run(settings: &Settings, backup_path: &Path) -> Result<()> {
+    let options = SqliteConnectOptions::from_str(&settings.database_url)?;
+    let database_path = options.get_filename();
+
+    // a restore never replaces a chain, move it away first
+    if database_path.exists() {
+        bail!(
+            "database {} already exists, stop the node and move it away before restoring",
+            database_path.display()
+        );
+    }
+    // SQLite would replay a stale log of an earlier database into the restored one
+    for suffix in ["-wal", "-shm"] {
+        let mut leftover = database_path.as_os_str().to_owned();
+        leftover.push(suffix);
+        if Path::new(&leftover).exists() {
+            bail!(
+                "{} is left over from an earlier database, remove it before restoring",
+                Path::new(&leftover).display()
+            );
+        }
+    }
+
+    let manifest = backup::check(backup_path).await?;
+
+    // copy then rename, so a failed copy never looks like a database
+    let mut partial = database_path.as_os_str().to_owned();
+    partial.push(".partial");
+    fs::copy(backup_path, &partial)?;
+    fs::rename(&partial, &database_path)?;
+
+    println!(
+        "Restored chain at height {} ({}) to {}",
+        manifest.height,
+        manifest.hash,
+        database_path.display()
+    );
+
+    Ok(())
+}
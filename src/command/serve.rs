@@ -0,0 +1,180 @@
+use anyhow::{bail, Result};
+use axum::Router;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::blockchain::{BlockLimits, Chain, Node};
+use crate::config::{Configuration, Mode, Settings};
+use crate::follower;
+use crate::forwarder::Forwarder;
+use crate::hasher::{chain_hash_algorithm, init_hash_algorithm};
+use crate::keys::NodeKey;
+use crate::persistence::sqlite::SqlitePersistence;
+use crate::persistence::NodePersistency;
+use crate::replication;
+use crate::router::{admin, api, ui};
+use crate::service;
+use crate::utils;
+
+/// Runs the block factory and serves the API and explorer
+pub async fn run(settings: Settings) -> Result<()> {
+    // query-only nodes without a primary read what another node stores
+    let persistence = match (&settings.mode, &settings.primary_url) {
+        (Mode::QueryOnly, None) => {
+            SqlitePersistence::connect_read_only(&settings.database_url).await?
+        }
+        _ => SqlitePersistence::connect(&settings.database_url).await?,
+    };
+    // query-only nodes with a primary pass transactions on to it
+    let forwarder = match (&settings.mode, &settings.primary_url) {
+        (Mode::QueryOnly, Some(primary_url)) => Some(Forwarder::new(primary_url)?),
+        _ => None,
+    };
+
+    // every block sealed here is signed, by one of the producers on a proof-of-authority chain
+    let producers = settings.producers()?;
+    let producer_key = match settings.mode {
+        Mode::QueryOnly => None,
+        _ => Some(settings.producer_key_path()?),
+    };
+    let key = match &producer_key {
+        Some(path) => Some(NodeKey::load_or_generate(path)?),
+        None => None,
+    };
+    if let (Some(producers), Some(key)) = (&producers, &key) {
+        if !producers.contains(&key.public_key()) {
+            bail!(
+                "producer key {} isn't one of the configured producers",
+                key.public_key()
+            );
+        }
+        if producers.len() > 1 && settings.peers.is_empty() {
+            bail!("`producers` needs the `peers` to pull their blocks from");
+        }
+    }
+
+    // a new chain hashes with the configured algorithm, an existing one keeps its own,
+    // one pulling its genesis from other nodes adopts theirs if none is configured
+    let pulls_genesis = match (&producers, &key) {
+        (Some(producers), Some(key)) => producers.producer_for(1) != key.public_key(),
+        _ => settings.primary_url.is_some(),
+    };
+    let has_blocks = persistence.read_block_data_by_height(1).await?.is_some();
+    let hash_algorithm = match (&settings.mode, &settings.primary_url) {
+        (Mode::QueryOnly, None) => Some(chain_hash_algorithm(&persistence).await?),
+        _ if pulls_genesis && !has_blocks && settings.hash_algorithm.is_none() => None,
+        _ => Some(init_hash_algorithm(&persistence, settings.hash_algorithm).await?),
+    };
+
+    // store in config struct
+    let shared_config = Arc::new(Configuration {
+        persistence,
+        chain: Chain::new(settings.block_time, hash_algorithm),
+        config_file: settings.config_file,
+        database_url: settings.database_url,
+        bind_address: settings.bind_address,
+        port: settings.port,
+        block_time: settings.block_time,
+        mode: settings.mode,
+        block_limits: BlockLimits {
+            max_transactions: settings.max_block_txs,
+            max_bytes: settings.max_block_bytes,
+            max_blocks_per_epoch: settings.blocks_per_epoch,
+        },
+        empty_block_policy: settings.empty_blocks,
+        heartbeat_interval: settings.heartbeat_interval,
+        block_trigger: settings.trigger,
+        seal_threshold: settings.seal_threshold,
+        nonce_limit: settings.nonce_limit,
+        admin_token: settings.admin_token,
+        assets_dir: settings.assets_dir,
+        backup_dir: settings.backup_dir,
+        primary_url: settings.primary_url,
+        forwarder,
+        producers,
+        producer_key,
+        public_key: key.as_ref().map(NodeKey::public_key),
+        peers: settings.peers,
+    });
+
+    // display config with beautiful table
+    utils::display_configuration(&shared_config);
+
+    // run the Chain in a task with Node runner
+    let config = Arc::clone(&shared_config);
+    let chain_handle = tokio::spawn(async move {
+        if let Mode::QueryOnly = config.mode {
+            return match &config.primary_url {
+                Some(primary_url) => {
+                    println!("Replicating from {primary_url}...");
+                    replication::replicate(&config, primary_url).await
+                }
+                None => {
+                    println!("Following chain tip...");
+                    follower::follow_tip(&config).await
+                }
+            };
+        }
+
+        println!("Spawning node runner...");
+
+        // run the node
+        let config = config;
+        let node = Node::new(
+            config.persistence.clone(),
+            config.nonce_limit,
+            &config.chain,
+            key,
+        );
+
+        // producers take turns, genesis included
+        if let Some(producers) = &config.producers {
+            return node.run_authority(&config, producers).await;
+        }
+
+        // create genesis block
+        node.store_genesis_block().await?;
+
+        node.run(&config).await
+    });
+
+    // send transactions buffered while the primary was unreachable
+    if shared_config.forwarder.is_some() {
+        let config = Arc::clone(&shared_config);
+        tokio::spawn(async move {
+            if let Some(forwarder) = &config.forwarder {
+                forwarder.flush().await;
+            }
+        });
+    }
+
+    // get routes and merge under one App route
+    let app = Router::new()
+        // route /assets (serve static files from /assets)
+        .merge(service::service(shared_config.assets_dir.as_deref())?)
+        // route /api/
+        .merge(api::router(shared_config.clone())?)
+        // route /api/admin/
+        .merge(admin::router(shared_config.clone())?)
+        // route / (for rendering templates)
+        .merge(ui::router(shared_config.clone())?);
+
+    // add global 404 page
+    let app = app.fallback(ui::not_found);
+
+    // serve block factory in a task
+    let config = Arc::clone(&shared_config);
+    let server_handle = tokio::spawn(async move {
+        println!("Spawning server...");
+        // run our app with hyper, listening on {--bind-address}:{--port}
+        let address = SocketAddr::new(config.bind_address, config.port);
+        let listener = tokio::net::TcpListener::bind(address).await?;
+        // run the server
+        axum::serve(listener, app).await
+    });
+
+    chain_handle.await??;
+    server_handle.await??;
+
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use serde_json::json;
+
+use crate::config::Settings;
+use crate::persistence::{sqlite::SqlitePersistence, NodePersistency};
+
+/// Prints chain totals and the tip
+pub async fn run(settings: &Settings) -> Result<()> {
+    let persistence = SqlitePersistence::connect_read_only(&settings.database_url).await?;
+
+    let stats = persistence.read_chain_stats().await?;
+    let tip = persistence.read_latest_block_data().await?;
+
+    let output = json!({
+        "blocks": stats.blocks,
+        "transactions": stats.transactions,
+        "total_fees": stats.total_fees,
+        "addresses": stats.addresses,
+        "tip": {
+            "height": tip.height,
+            "hash": tip.hash,
+            "created_at": tip.created_at(),
+        },
+    });
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+
+use crate::config::Settings;
+use crate::persistence::sqlite::SqlitePersistence;
+use crate::verifier::verify_chain;
+
+/// Checks the stored chain from genesis to tip
+pub async fn run(settings: &Settings) -> Result<()> {
+    let persistence = SqlitePersistence::connect_read_only(&settings.database_url).await?;
+
+    let verifier = verify_chain(&persistence, settings.producers()?).await?;
+    // verify_chain fails on an empty chain
+    let tip = verifier.tip().unwrap();
+
+    println!(
+        "Verified {} blocks and {} transactions, tip at height {} ({})",
+        verifier.blocks(),
+        verifier.transactions(),
+        tip.height,
+        tip.hash
+    );
+
+    Ok(())
+}
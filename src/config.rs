@@ -1,16 +1,508 @@
-use crate::blockchain::Chain;
-use clap::ValueEnum;
+use crate::authority::Producers;
+use crate::blockchain::{BlockLimits, Chain};
+use crate::forwarder::Forwarder;
+use crate::hasher::HashAlgorithm;
+use crate::persistence::sqlite::SqlitePersistence;
+use crate::utils::parse_block_time;
+use anyhow::{bail, Context, Result};
+use clap::{ArgMatches, ValueEnum};
+use serde::{Deserialize, Deserializer};
+use sqlx::sqlite::SqliteConnectOptions;
+use std::env;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
-#[derive(Clone, Debug, ValueEnum)]
+// prefix of the environment variables that override the configuration file
+const ENV_PREFIX: &str = "BLOCK_FACTORY_";
+
+#[derive(Clone, Debug, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
 pub enum Mode {
     Full,
     FactoryOnly,
     QueryOnly,
 }
 
+/// What the factory does when a block epoch ends with an empty pool
+#[derive(Clone, Debug, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmptyBlockPolicy {
+    /// Seal an empty block every epoch
+    Always,
+    /// Seal an empty block once no block was sealed for the heartbeat interval
+    Heartbeat,
+    /// Don't seal empty blocks
+    Skip,
+}
+
+/// What makes the factory seal a block
+/// (on-demand seals through the admin API work with every trigger)
+#[derive(Clone, Debug, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlockTrigger {
+    /// Seal at the end of every block time
+    Time,
+    /// Seal when the pool reaches the seal threshold
+    Size,
+    /// Seal on block time or seal threshold, whichever comes first
+    Hybrid,
+    /// Seal only on demand
+    Manual,
+}
+
+impl BlockTrigger {
+    /// Returns true if blocks are sealed on block time
+    pub fn is_timed(&self) -> bool {
+        matches!(self, BlockTrigger::Time | BlockTrigger::Hybrid)
+    }
+
+    /// Returns true if blocks are sealed on pool size
+    pub fn is_sized(&self) -> bool {
+        matches!(self, BlockTrigger::Size | BlockTrigger::Hybrid)
+    }
+}
+
+/// Node settings layered from a TOML file, `BLOCK_FACTORY_*`
+/// environment variables and CLI flags, each overriding the one before
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// File the settings were loaded from, if any
+    #[serde(skip)]
+    pub config_file: Option<PathBuf>,
+    pub database_url: String,
+    pub bind_address: IpAddr,
+    pub port: u16,
+    #[serde(deserialize_with = "deserialize_block_time")]
+    pub block_time: Duration,
+    pub max_block_txs: Option<usize>,
+    pub max_block_bytes: Option<usize>,
+    pub blocks_per_epoch: u32,
+    pub empty_blocks: EmptyBlockPolicy,
+    pub heartbeat_interval: u32,
+    pub trigger: BlockTrigger,
+    pub seal_threshold: usize,
+    /// Block nonces are drawn from `0..nonce_limit`
+    pub nonce_limit: u32,
+    /// Hash algorithm of a new chain (SHA-256 if unset),
+    /// existing chains keep the one recorded at genesis
+    pub hash_algorithm: Option<HashAlgorithm>,
+    pub admin_token: Option<String>,
+    pub assets_dir: Option<PathBuf>,
+    /// Directory backups are written to
+    pub backup_dir: PathBuf,
+    pub mode: Mode,
+    /// API of the factory a query-only node replicates from
+    pub primary_url: Option<String>,
+    /// Public keys taking turns sealing blocks, in order (proof-of-authority)
+    pub producers: Vec<String>,
+    /// Key file this node signs the blocks it seals with,
+    /// `node.key` next to the database if unset
+    pub producer_key: Option<PathBuf>,
+    /// APIs of the other producers, blocks are pulled from them
+    pub peers: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            config_file: None,
+            database_url: String::from("sqlite:db/blocks.db"),
+            bind_address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8080,
+            block_time: Duration::from_secs(1),
+            max_block_txs: None,
+            max_block_bytes: None,
+            blocks_per_epoch: 1,
+            empty_blocks: EmptyBlockPolicy::Skip,
+            heartbeat_interval: 60,
+            trigger: BlockTrigger::Time,
+            seal_threshold: 100,
+            nonce_limit: 100,
+            hash_algorithm: None,
+            admin_token: None,
+            assets_dir: None,
+            backup_dir: PathBuf::from("backups"),
+            mode: Mode::Full,
+            primary_url: None,
+            producers: vec![],
+            producer_key: None,
+            peers: vec![],
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the configuration file (`--config` or `BLOCK_FACTORY_CONFIG`),
+    /// overlays the environment and CLI flags, then checks the result
+    /// (`matches` of nested subcommands go last, they win over their parents)
+    pub fn load(matches: &[&ArgMatches]) -> Result<Self> {
+        let config_file = matches
+            .iter()
+            .rev()
+            .find_map(|matches| flag::<PathBuf>(matches, "CONFIG"))
+            .cloned()
+            .or_else(|| env_var("BLOCK_FACTORY_CONFIG").map(PathBuf::from));
+
+        let mut settings = match &config_file {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        settings.config_file = config_file;
+
+        settings.apply_env()?;
+        for matches in matches {
+            settings.apply_matches(matches);
+        }
+
+        // an empty token disables the admin API, wherever it was set
+        settings.admin_token = settings.admin_token.filter(|token| !token.is_empty());
+        // API paths are appended to them
+        settings.primary_url = settings
+            .primary_url
+            .map(|url| url.trim_end_matches('/').to_owned());
+        for peer in &mut settings.peers {
+            *peer = peer.trim_end_matches('/').to_owned();
+        }
+
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Producer rotation, `None` unless it's a proof-of-authority chain
+    pub fn producers(&self) -> Result<Option<Producers>> {
+        if self.producers.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            Producers::new(&self.producers).context("invalid `producers`")?,
+        ))
+    }
+
+    /// Key file sealed blocks are signed with, created if missing
+    pub fn producer_key_path(&self) -> Result<PathBuf> {
+        if let Some(path) = &self.producer_key {
+            return Ok(path.clone());
+        }
+
+        let options = SqliteConnectOptions::from_str(&self.database_url)?;
+        let database = options.get_filename();
+        Ok(database.with_file_name("node.key"))
+    }
+
+    fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        // unprefixed, as in .env, the prefixed names win
+        if let Some(database_url) = env_var("DATABASE_URL") {
+            self.database_url = database_url;
+        }
+        if let Some(database_url) = env_var(&format!("{ENV_PREFIX}DATABASE_URL")) {
+            self.database_url = database_url;
+        }
+        if let Ok(admin_token) = env::var("ADMIN_TOKEN") {
+            self.admin_token = Some(admin_token);
+        }
+        if let Ok(admin_token) = env::var(format!("{ENV_PREFIX}ADMIN_TOKEN")) {
+            self.admin_token = Some(admin_token);
+        }
+
+        if let Some(bind_address) = parse_env("BIND_ADDRESS", parse_value)? {
+            self.bind_address = bind_address;
+        }
+        if let Some(port) = parse_env("PORT", parse_value)? {
+            self.port = port;
+        }
+        if let Some(block_time) = parse_env("BLOCK_TIME", parse_block_time)? {
+            self.block_time = block_time;
+        }
+        if let Some(max_block_txs) = parse_env("MAX_BLOCK_TXS", parse_value)? {
+            self.max_block_txs = Some(max_block_txs);
+        }
+        if let Some(max_block_bytes) = parse_env("MAX_BLOCK_BYTES", parse_value)? {
+            self.max_block_bytes = Some(max_block_bytes);
+        }
+        if let Some(blocks_per_epoch) = parse_env("BLOCKS_PER_EPOCH", parse_value)? {
+            self.blocks_per_epoch = blocks_per_epoch;
+        }
+        if let Some(empty_blocks) = parse_env("EMPTY_BLOCKS", parse_enum)? {
+            self.empty_blocks = empty_blocks;
+        }
+        if let Some(heartbeat_interval) = parse_env("HEARTBEAT_INTERVAL", parse_value)? {
+            self.heartbeat_interval = heartbeat_interval;
+        }
+        if let Some(trigger) = parse_env("TRIGGER", parse_enum)? {
+            self.trigger = trigger;
+        }
+        if let Some(seal_threshold) = parse_env("SEAL_THRESHOLD", parse_value)? {
+            self.seal_threshold = seal_threshold;
+        }
+        if let Some(nonce_limit) = parse_env("NONCE_LIMIT", parse_value)? {
+            self.nonce_limit = nonce_limit;
+        }
+        if let Some(hash_algorithm) = parse_env("HASH_ALGORITHM", parse_enum)? {
+            self.hash_algorithm = Some(hash_algorithm);
+        }
+        if let Some(assets_dir) = env_var(&format!("{ENV_PREFIX}ASSETS_DIR")) {
+            self.assets_dir = Some(PathBuf::from(assets_dir));
+        }
+        if let Some(backup_dir) = env_var(&format!("{ENV_PREFIX}BACKUP_DIR")) {
+            self.backup_dir = PathBuf::from(backup_dir);
+        }
+        if let Some(mode) = parse_env("MODE", parse_enum)? {
+            self.mode = mode;
+        }
+        if let Some(primary_url) = env_var(&format!("{ENV_PREFIX}PRIMARY_URL")) {
+            self.primary_url = Some(primary_url);
+        }
+        if let Some(producers) = env_var(&format!("{ENV_PREFIX}PRODUCERS")) {
+            self.producers = split_list(&producers);
+        }
+        if let Some(producer_key) = env_var(&format!("{ENV_PREFIX}PRODUCER_KEY")) {
+            self.producer_key = Some(PathBuf::from(producer_key));
+        }
+        if let Some(peers) = env_var(&format!("{ENV_PREFIX}PEERS")) {
+            self.peers = split_list(&peers);
+        }
+
+        Ok(())
+    }
+
+    // flags are already parsed by clap, only the ones passed are set
+    fn apply_matches(&mut self, matches: &ArgMatches) {
+        if let Some(database_url) = flag::<String>(matches, "DATABASEURL") {
+            self.database_url = database_url.to_owned();
+        }
+        if let Some(bind_address) = flag::<IpAddr>(matches, "BINDADDRESS") {
+            self.bind_address = *bind_address;
+        }
+        if let Some(port) = flag::<u16>(matches, "PORT") {
+            self.port = *port;
+        }
+        if let Some(block_time) = flag::<Duration>(matches, "BLOCKTIME") {
+            self.block_time = *block_time;
+        }
+        if let Some(max_block_txs) = flag::<usize>(matches, "MAXBLOCKTXS") {
+            self.max_block_txs = Some(*max_block_txs);
+        }
+        if let Some(max_block_bytes) = flag::<usize>(matches, "MAXBLOCKBYTES") {
+            self.max_block_bytes = Some(*max_block_bytes);
+        }
+        if let Some(blocks_per_epoch) = flag::<u32>(matches, "BLOCKSPEREPOCH") {
+            self.blocks_per_epoch = *blocks_per_epoch;
+        }
+        if let Some(empty_blocks) = flag::<EmptyBlockPolicy>(matches, "EMPTYBLOCKS") {
+            self.empty_blocks = empty_blocks.to_owned();
+        }
+        if let Some(heartbeat_interval) = flag::<u32>(matches, "HEARTBEAT") {
+            self.heartbeat_interval = *heartbeat_interval;
+        }
+        if let Some(trigger) = flag::<BlockTrigger>(matches, "TRIGGER") {
+            self.trigger = trigger.to_owned();
+        }
+        if let Some(seal_threshold) = flag::<usize>(matches, "SEALTHRESHOLD") {
+            self.seal_threshold = *seal_threshold;
+        }
+        if let Some(nonce_limit) = flag::<u32>(matches, "NONCELIMIT") {
+            self.nonce_limit = *nonce_limit;
+        }
+        if let Some(hash_algorithm) = flag::<HashAlgorithm>(matches, "HASHALGORITHM") {
+            self.hash_algorithm = Some(*hash_algorithm);
+        }
+        if let Some(assets_dir) = flag::<PathBuf>(matches, "ASSETSDIR") {
+            self.assets_dir = Some(assets_dir.to_owned());
+        }
+        if let Some(backup_dir) = flag::<PathBuf>(matches, "BACKUPDIR") {
+            self.backup_dir = backup_dir.to_owned();
+        }
+        if let Some(mode) = flag::<Mode>(matches, "MODE") {
+            self.mode = mode.to_owned();
+        }
+        if let Some(primary_url) = flag::<String>(matches, "PRIMARYURL") {
+            self.primary_url = Some(primary_url.to_owned());
+        }
+        if let Some(producers) = flags::<String>(matches, "PRODUCERS") {
+            self.producers = producers;
+        }
+        if let Some(producer_key) = flag::<PathBuf>(matches, "PRODUCERKEY") {
+            self.producer_key = Some(producer_key.to_owned());
+        }
+        if let Some(peers) = flags::<String>(matches, "PEERS") {
+            self.peers = peers;
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !self.database_url.starts_with("sqlite:") {
+            bail!("`database_url` must be a sqlite: URL (only SQLite for now)");
+        }
+        if self.port == 0 {
+            bail!("`port` must be at least 1");
+        }
+
+        let counts = [
+            ("max_block_txs", self.max_block_txs.unwrap_or(1)),
+            ("max_block_bytes", self.max_block_bytes.unwrap_or(1)),
+            ("blocks_per_epoch", self.blocks_per_epoch as usize),
+            ("heartbeat_interval", self.heartbeat_interval as usize),
+            ("seal_threshold", self.seal_threshold),
+            ("nonce_limit", self.nonce_limit as usize),
+        ];
+        for (key, count) in counts {
+            if count == 0 {
+                bail!("`{key}` must be at least 1");
+            }
+        }
+
+        if let Some(primary_url) = &self.primary_url {
+            if !primary_url.starts_with("http://") && !primary_url.starts_with("https://") {
+                bail!("`primary_url` must be an http:// or https:// URL");
+            }
+            if !matches!(self.mode, Mode::QueryOnly) {
+                bail!("`primary_url` is only used with `mode = \"query-only\"`");
+            }
+        }
+
+        // producers only need a key and peers to serve
+        if self.producers()?.is_none() && !self.peers.is_empty() {
+            bail!("`peers` are only used with `producers`");
+        }
+        for peer in &self.peers {
+            if !peer.starts_with("http://") && !peer.starts_with("https://") {
+                bail!("peer `{peer}` must be an http:// or https:// URL");
+            }
+        }
+
+        if let Some(dir) = &self.assets_dir {
+            if !dir.is_dir() {
+                bail!("`assets_dir` {} is not a directory", dir.display());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// flags the (sub)command doesn't define count as unset
+fn flag<'a, T>(matches: &'a ArgMatches, id: &str) -> Option<&'a T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    matches.try_get_one::<T>(id).ok().flatten()
+}
+
+// like `flag`, for flags taking a list
+fn flags<T>(matches: &ArgMatches, id: &str) -> Option<Vec<T>>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    matches
+        .try_get_many::<T>(id)
+        .ok()
+        .flatten()
+        .map(|values| values.cloned().collect())
+}
+
+// comma separated, e.g. `BLOCK_FACTORY_PEERS=http://a:8080,http://b:8080`
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+// reads an environment variable, empty counts as unset
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+// reads and parses `BLOCK_FACTORY_{key}`
+fn parse_env<T>(key: &str, parse: impl Fn(&str) -> Result<T, String>) -> Result<Option<T>> {
+    let name = format!("{ENV_PREFIX}{key}");
+    match env_var(&name) {
+        Some(value) => match parse(&value) {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(err) => bail!("invalid {name} \"{value}\": {err}"),
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_value<T>(value: &str) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    value.trim().parse().map_err(|err: T::Err| err.to_string())
+}
+
+fn parse_enum<T: ValueEnum>(value: &str) -> Result<T, String> {
+    T::from_str(value.trim(), true)
+}
+
+// same format as `--block-time`, a string like "250ms" or whole seconds
+fn deserialize_block_time<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BlockTime {
+        Secs(u64),
+        Text(String),
+    }
+
+    let block_time = match BlockTime::deserialize(deserializer)? {
+        BlockTime::Secs(secs) => secs.to_string(),
+        BlockTime::Text(text) => text,
+    };
+    parse_block_time(&block_time).map_err(serde::de::Error::custom)
+}
+
 pub struct Configuration {
-    pub port: u32,
-    pub block_time: u32,
+    /// File the settings were loaded from, if any
+    pub config_file: Option<PathBuf>,
+    pub database_url: String,
+    pub bind_address: IpAddr,
+    pub port: u16,
+    pub block_time: Duration,
     pub mode: Mode,
+    pub block_limits: BlockLimits,
+    pub empty_block_policy: EmptyBlockPolicy,
+    pub heartbeat_interval: u32,
+    pub block_trigger: BlockTrigger,
+    pub seal_threshold: usize,
+    /// Block nonces are drawn from `0..nonce_limit`
+    pub nonce_limit: u32,
+    /// Bearer token for the admin API (`None` disables it)
+    pub admin_token: Option<String>,
+    /// Directory whose files replace the embedded assets (for development)
+    pub assets_dir: Option<PathBuf>,
+    /// Directory backups are written to
+    pub backup_dir: PathBuf,
+    /// API of the factory a query-only node replicates from
+    /// (`None` to follow a database another node writes to)
+    pub primary_url: Option<String>,
+    /// Hands submitted transactions to the primary (query-only nodes with one)
+    pub forwarder: Option<Forwarder>,
+    /// Producer rotation of a proof-of-authority chain
+    pub producers: Option<Producers>,
+    /// Key file the blocks sealed by this node are signed with
+    /// (`None` for query-only nodes, they don't seal)
+    pub producer_key: Option<PathBuf>,
+    /// Public key of `producer_key`
+    pub public_key: Option<String>,
+    /// APIs of the other producers
+    pub peers: Vec<String>,
     pub chain: Chain,
+    pub persistence: SqlitePersistence,
 }
@@ -0,0 +1,191 @@
+// Canonical encodings of what block and transaction hashes cover,
+// integers are big-endian:
+//
+//   header       domain "block-factory/header": str, version: u32,
+//                id: str, nonce: u32, height: u32, prev_block_hash: str,
+//                transactions: u32 count, then each transaction hash: str
+//   transaction  domain "block-factory/transaction": str,
+//                id, from, to: str, instruction: bytes, fee: u32
+//
+// str and bytes are a u32 length followed by the data, so no two field
+// values encode the same. The transaction hashes are in block order, so the
+// block hash (and the signature over it) covers the transactions as well.
+// Headers before version 2 and their transactions
+// hashed the fields concatenated as text, kept to verify older blocks.
+// Test vectors are in the README.
+
+/// Header format of the blocks sealed by this version
+pub const BLOCK_HEADER_VERSION: u32 = 2;
+/// Header format of blocks sealed before headers were versioned
+pub const LEGACY_HEADER_VERSION: u32 = 1;
+
+const HEADER_DOMAIN: &str = "block-factory/header";
+const TRANSACTION_DOMAIN: &str = "block-factory/transaction";
+
+/// Bytes hashed for a block header in the current format,
+/// with the hashes of the block's transactions in block order
+pub fn block_header(
+    id: &str,
+    nonce: u32,
+    height: u32,
+    prev_block_hash: &str,
+    transaction_hashes: &[&str],
+) -> Vec<u8> {
+    let mut encoded = vec![];
+    put_bytes(&mut encoded, HEADER_DOMAIN.as_bytes());
+    put_u32(&mut encoded, BLOCK_HEADER_VERSION);
+    put_bytes(&mut encoded, id.as_bytes());
+    put_u32(&mut encoded, nonce);
+    put_u32(&mut encoded, height);
+    put_bytes(&mut encoded, prev_block_hash.as_bytes());
+    put_u32(&mut encoded, transaction_hashes.len() as u32);
+    for hash in transaction_hashes {
+        put_bytes(&mut encoded, hash.as_bytes());
+    }
+    encoded
+}
+
+/// Bytes hashed for a transaction in a block with a current header
+pub fn transaction(id: &str, from: &str, to: &str, instruction: &[u8], fee: u32) -> Vec<u8> {
+    let mut encoded = vec![];
+    put_bytes(&mut encoded, TRANSACTION_DOMAIN.as_bytes());
+    put_bytes(&mut encoded, id.as_bytes());
+    put_bytes(&mut encoded, from.as_bytes());
+    put_bytes(&mut encoded, to.as_bytes());
+    put_bytes(&mut encoded, instruction);
+    put_u32(&mut encoded, fee);
+    encoded
+}
+
+/// Bytes hashed for a block header of [`LEGACY_HEADER_VERSION`], ambiguous
+/// (nonce 12 at height 3 encodes like nonce 1 at height 23) and without
+/// the transactions
+pub fn legacy_block_header(id: &str, nonce: u32, height: u32, prev_block_hash: &str) -> Vec<u8> {
+    format!("{id}{nonce}{height}{prev_block_hash}").into_bytes()
+}
+
+/// Bytes hashed for a transaction in a block of [`LEGACY_HEADER_VERSION`],
+/// `None` if the instruction isn't valid UTF-8
+pub fn legacy_transaction(
+    id: &str,
+    from: &str,
+    to: &str,
+    instruction: &[u8],
+    fee: u32,
+) -> Option<Vec<u8>> {
+    let instruction = std::str::from_utf8(instruction).ok()?;
+    Some(format!("{id}{from}{to}{instruction}{fee}").into_bytes())
+}
+
+fn put_u32(encoded: &mut Vec<u8>, value: u32) {
+    encoded.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_bytes(encoded: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(encoded, bytes.len() as u32);
+    encoded.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::HashAlgorithm;
+
+    // the test vectors of the README
+    const ID: &str = "0192a0c4-8e00-7000-8000-000000000000";
+
+    fn digests(encoded: &[u8]) -> [String; 3] {
+        [
+            HashAlgorithm::Sha256.digest(encoded),
+            HashAlgorithm::Blake3.digest(encoded),
+            HashAlgorithm::Keccak256.digest(encoded),
+        ]
+    }
+
+    #[test]
+    fn block_header_matches_test_vector() {
+        let encoded = block_header(ID, 12, 3, "ab", &[]);
+
+        assert_eq!(
+            hex::encode(&encoded),
+            "00000014626c6f636b2d666163746f72792f686561646572000000020000002430313932613063342d386530302d373030302d383030302d3030303030303030303030300000000c0000000300000002616200000000"
+        );
+        assert_eq!(
+            digests(&encoded),
+            [
+                "0121b1408357305412c4e3cf9b149878a9b7cdac2276cfa34ecb090fa3b9c802",
+                "9fd9168982c00f4fc528f1d8a740df57b226c2ba3f53db16a1ffdda47fc01575",
+                "cd42abc70cc5da32d855a8d480bb0db13c4871df0d60813b8858640b4077f64f",
+            ]
+        );
+    }
+
+    #[test]
+    fn block_headers_with_the_same_legacy_preimage_differ() {
+        assert_eq!(
+            legacy_block_header(ID, 12, 3, "ab"),
+            legacy_block_header(ID, 1, 23, "ab")
+        );
+
+        let encoded = block_header(ID, 1, 23, "ab", &[]);
+        assert_eq!(
+            hex::encode(&encoded),
+            "00000014626c6f636b2d666163746f72792f686561646572000000020000002430313932613063342d386530302d373030302d383030302d303030303030303030303030000000010000001700000002616200000000"
+        );
+        assert_eq!(
+            digests(&encoded),
+            [
+                "dd1a04b2202728b42e00fc9c231179af1de8ea50cf0d8d9db54438b3144aa8d9",
+                "398acb2ee78a05d46c1cc9a186e3b05a44586127a73779e95620d56ee381b55f",
+                "741a5ae99f46ac9b8c26a0acbb9338e5f94ff7f8baf44df6dc401ee682375196",
+            ]
+        );
+        assert_ne!(encoded, block_header(ID, 12, 3, "ab", &[]));
+    }
+
+    #[test]
+    fn transaction_matches_test_vector() {
+        let encoded = transaction(ID, "alice", "bob", b"hi", 1);
+
+        assert_eq!(
+            hex::encode(&encoded),
+            "00000019626c6f636b2d666163746f72792f7472616e73616374696f6e0000002430313932613063342d386530302d373030302d383030302d30303030303030303030303000000005616c69636500000003626f6200000002686900000001"
+        );
+        assert_eq!(
+            digests(&encoded),
+            [
+                "e227be58006e83ad8bc1f163405ce89c7de510bf5f696cf73457410c56cfc9d1",
+                "24422543e4980116bb22c0033a2197c9e58576ee7bc6815481aaedb4ab42dc8e",
+                "fa964c7afeb946eb049ab4090744b5c2035104685a9e59b3960e14e418904675",
+            ]
+        );
+    }
+
+    #[test]
+    fn block_header_covers_its_transactions() {
+        let transaction = transaction(ID, "alice", "bob", b"hi", 1);
+        let hashes = digests(&transaction);
+        let headers = hashes
+            .each_ref()
+            .map(|hash| block_header(ID, 12, 3, "ab", &[hash.as_str()]));
+
+        assert_eq!(
+            hex::encode(&headers[0]),
+            "00000014626c6f636b2d666163746f72792f686561646572000000020000002430313932613063342d386530302d373030302d383030302d3030303030303030303030300000000c00000003000000026162000000010000004065323237626535383030366538336164386263316631363334303563653839633764653531306266356636393663663733343537343130633536636663396431"
+        );
+        let header_hashes = [
+            HashAlgorithm::Sha256.digest(&headers[0]),
+            HashAlgorithm::Blake3.digest(&headers[1]),
+            HashAlgorithm::Keccak256.digest(&headers[2]),
+        ];
+        assert_eq!(
+            header_hashes,
+            [
+                "c858d957cc465c2fb0a559be7860efb8eed79d5d01d9eeec3ac0cce3be121f6b",
+                "5630f2c56a158c3824b2253a72080f1902f8ea0a90837973a180c9954cb5a56d",
+                "2f18bae9c691e8ad1e81fbd30414e8a6d6e248472916f908816c5534fdaa0425",
+            ]
+        );
+        assert_ne!(headers[0], block_header(ID, 12, 3, "ab", &[]));
+    }
+}
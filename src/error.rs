@@ -4,4 +4,6 @@ use thiserror::Error;
 pub enum BlockFactoryError {
     #[error("genesis block missing")]
     MissingGenesis,
+    #[error("invalid block at height {height}: {reason}")]
+    InvalidBlock { height: u32, reason: String },
 }
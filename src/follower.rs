@@ -0,0 +1,64 @@
+use anyhow::Result;
+use std::time::Duration;
+use tokio::time::{self, MissedTickBehavior};
+
+use crate::config::Configuration;
+use crate::persistence::{walker::ChainWalker, NodePersistency};
+
+// how often a query-only node checks the database for blocks stored by the writer
+const TIP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Keeps the chain snapshot of a query-only node on the tip stored by
+/// another node writing to the same database, publishing every new block
+pub async fn follow_tip(config: &Configuration) -> Result<()> {
+    config.chain.load_snapshot(&config.persistence).await?;
+
+    // `data_version` only changes for commits made by other connections,
+    // so the same connection has to ask every time
+    let mut connection = config.persistence.pool.acquire().await?;
+    let mut data_version = None;
+
+    let mut interval = time::interval(TIP_POLL_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+
+        let version: i64 = sqlx::query_scalar("PRAGMA data_version")
+            .fetch_one(&mut *connection)
+            .await?;
+        if data_version == Some(version) {
+            continue;
+        }
+        data_version = Some(version);
+
+        catch_up(config).await?;
+    }
+}
+
+// publishes the blocks stored past the snapshot tip
+async fn catch_up(config: &Configuration) -> Result<()> {
+    let snapshot = config.chain.snapshot();
+
+    if let Some(tip) = snapshot.tip() {
+        let stored = config
+            .persistence
+            .read_block_data_by_height(tip.height)
+            .await?;
+        // the chain was replaced under us (e.g. restored), start over
+        if stored.is_none_or(|block| block.hash != tip.hash) {
+            println!(
+                "Chain tip {} at height {} is gone, reloading",
+                tip.hash, tip.height
+            );
+            return config.chain.load_snapshot(&config.persistence).await;
+        }
+    }
+
+    let mut walker = ChainWalker::from_height(&config.persistence, snapshot.height() + 1);
+    while let Some(sealed) = walker.next().await? {
+        config.chain.publish_sealed_block(sealed);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use anyhow::{bail, Result};
+use axum::{body::Bytes, http::StatusCode};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tokio::time;
+
+// longest a forwarded request may take, the primary may or may not have
+// pooled a transaction whose request timed out, so those aren't retried
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(5);
+// attempts at reaching the primary before a request is buffered
+const FORWARD_ATTEMPTS: u32 = 3;
+// wait before the second attempt, doubled for each one after
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(100);
+// requests kept while the primary is unreachable
+const FORWARD_BUFFER_CAPACITY: usize = 1000;
+// wait between attempts at flushing the buffer
+const FLUSH_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A transaction request on its way to the primary
+#[derive(Clone, Debug)]
+pub struct ForwardRequest {
+    /// API path on the primary, e.g. `/api/transaction`
+    pub path: &'static str,
+    pub content_type: String,
+    pub body: Bytes,
+}
+
+/// What became of a forwarded request
+pub enum Forwarded {
+    /// The primary answered, passed back to the client as is
+    Answered { status: StatusCode, body: Value },
+    /// The primary is unreachable, the request is sent once it's back
+    Buffered { position: usize },
+}
+
+enum SendError {
+    // the request never reached the primary, safe to send again
+    Unreachable(String),
+    // the primary may have handled the request
+    Failed(String),
+}
+
+/// Forwards transactions submitted to a query-only node to its primary,
+/// buffering them while the primary is unreachable
+pub struct Forwarder {
+    client: reqwest::Client,
+    primary_url: String,
+    // oldest first, sent before any newer request to keep their order
+    buffer: Mutex<VecDeque<ForwardRequest>>,
+    flush_wanted: Notify,
+}
+
+impl Forwarder {
+    pub fn new(primary_url: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(FORWARD_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            client,
+            primary_url: primary_url.to_owned(),
+            buffer: Mutex::new(VecDeque::new()),
+            flush_wanted: Notify::new(),
+        })
+    }
+
+    pub fn primary_url(&self) -> &str {
+        &self.primary_url
+    }
+
+    /// Amount of requests waiting for the primary
+    pub async fn buffered(&self) -> usize {
+        self.buffer.lock().await.len()
+    }
+
+    /// Sends `request` to the primary, retrying while it's unreachable,
+    /// then buffers it if it still is
+    pub async fn forward(&self, request: ForwardRequest) -> Result<Forwarded> {
+        // requests buffered earlier go first
+        if !self.buffer.lock().await.is_empty() {
+            return self.push(request).await;
+        }
+
+        let mut retry_delay = FIRST_RETRY_DELAY;
+        for attempt in 1..=FORWARD_ATTEMPTS {
+            match self.send(&request).await {
+                Ok((status, body)) => return Ok(Forwarded::Answered { status, body }),
+                Err(SendError::Failed(err)) => bail!(err),
+                Err(SendError::Unreachable(err)) => {
+                    if attempt == FORWARD_ATTEMPTS {
+                        println!("Primary {} unreachable: {err}", self.primary_url);
+                    } else {
+                        time::sleep(retry_delay).await;
+                        retry_delay *= 2;
+                    }
+                }
+            }
+        }
+
+        self.push(request).await
+    }
+
+    /// Sends buffered requests to the primary, oldest first,
+    /// whenever there are some and it's reachable
+    pub async fn flush(&self) {
+        loop {
+            let Some(request) = self.buffer.lock().await.front().cloned() else {
+                self.flush_wanted.notified().await;
+                continue;
+            };
+
+            match self.send(&request).await {
+                Ok((status, body)) => {
+                    // no client is waiting for the answer anymore
+                    if !status.is_success() {
+                        println!(
+                            "Primary refused buffered request to {}: {}",
+                            request.path, body["message"]
+                        );
+                    }
+                }
+                Err(SendError::Failed(err)) => {
+                    println!("Dropped buffered request to {}: {err}", request.path);
+                }
+                Err(SendError::Unreachable(_)) => {
+                    time::sleep(FLUSH_RETRY_DELAY).await;
+                    continue;
+                }
+            }
+
+            self.buffer.lock().await.pop_front();
+        }
+    }
+
+    async fn push(&self, request: ForwardRequest) -> Result<Forwarded> {
+        let mut buffer = self.buffer.lock().await;
+        if buffer.len() >= FORWARD_BUFFER_CAPACITY {
+            bail!(
+                "primary {} is unreachable and the forward buffer is full",
+                self.primary_url
+            );
+        }
+        buffer.push_back(request);
+        self.flush_wanted.notify_one();
+
+        Ok(Forwarded::Buffered {
+            position: buffer.len(),
+        })
+    }
+
+    async fn send(&self, request: &ForwardRequest) -> Result<(StatusCode, Value), SendError> {
+        let response = self
+            .client
+            .put(format!("{}{}", self.primary_url, request.path))
+            .header("content-type", &request.content_type)
+            .body(request.body.clone())
+            .send()
+            .await
+            .map_err(|err| {
+                if err.is_connect() {
+                    SendError::Unreachable(err.to_string())
+                } else {
+                    SendError::Failed(err.to_string())
+                }
+            })?;
+
+        let status = response.status();
+        // a proxy in front of the primary couldn't reach it either
+        if matches!(
+            status,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE
+        ) {
+            return Err(SendError::Unreachable(format!("primary answered {status}")));
+        }
+
+        let body = response.json::<Value>().await.unwrap_or_else(|err| {
+            json!({
+                "message": format!("invalid response from primary: {err}"),
+                "status": "ERROR",
+            })
+        });
+
+        Ok((status, body))
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::Configuration;
+use crate::persistence::NodePersistency;
+
+// chain metadata key the algorithm is recorded under
+const HASH_ALGORITHM_KEY: &str = "hash_algorithm";
+
+/// Hash function of a chain's block and transaction hashes,
+/// chosen at genesis and recorded in the chain metadata
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    /// Hex encoded hash of `data`
+    pub fn digest(&self, data: &[u8]) -> String {
+        match self {
+            HashAlgorithm::Sha256 => sha256::digest(data),
+            HashAlgorithm::Blake3 => blake3::hash(data).to_hex().to_string(),
+            HashAlgorithm::Keccak256 => hex::encode(Keccak256::digest(data)),
+        }
+    }
+
+    /// Name as written in settings, chain metadata and chain files
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Keccak256 => "keccak256",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashAlgorithm::Sha256 => write!(f, "SHA-256"),
+            HashAlgorithm::Blake3 => write!(f, "BLAKE3"),
+            HashAlgorithm::Keccak256 => write!(f, "Keccak-256"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match <Self as ValueEnum>::from_str(name.trim(), true) {
+            Ok(algorithm) => Ok(algorithm),
+            Err(_) => bail!("unknown hash algorithm {name}, expected sha256, blake3 or keccak256"),
+        }
+    }
+}
+
+/// Algorithm recorded for the chain in `persistence`,
+/// chains from before it was recorded hash with SHA-256
+pub async fn chain_hash_algorithm(persistence: &impl NodePersistency) -> Result<HashAlgorithm> {
+    match persistence.read_chain_metadata(HASH_ALGORITHM_KEY).await? {
+        Some(name) => name.parse(),
+        None => Ok(HashAlgorithm::Sha256),
+    }
+}
+
+/// Records the algorithm of the chain in `persistence`: `configured` while
+/// nothing is hashed yet, SHA-256 if it's unset, an existing chain keeps
+/// its own and `configured` must match it
+pub async fn init_hash_algorithm(
+    persistence: &impl NodePersistency,
+    configured: Option<HashAlgorithm>,
+) -> Result<HashAlgorithm> {
+    let has_blocks = persistence.read_block_data_by_height(1).await?.is_some();
+    let algorithm = match configured {
+        Some(configured) if !has_blocks => configured,
+        _ => chain_hash_algorithm(persistence).await?,
+    };
+
+    if let Some(configured) = configured {
+        if configured != algorithm {
+            bail!(
+                "the chain hashes with {algorithm}, not {configured}, \
+                 the hash algorithm is chosen at genesis"
+            );
+        }
+    }
+
+    persistence
+        .store_chain_metadata(HASH_ALGORITHM_KEY, algorithm.name())
+        .await?;
+
+    Ok(algorithm)
+}
+
+/// Algorithm to check the blocks of a node hashing with `offered` against:
+/// the chain's own, or `offered` on a new chain that has none yet,
+/// which adopts and records it
+pub async fn adopt_hash_algorithm(
+    config: &Configuration,
+    offered: HashAlgorithm,
+) -> Result<HashAlgorithm> {
+    if let Some(algorithm) = config.chain.hash_algorithm() {
+        return Ok(algorithm);
+    }
+
+    init_hash_algorithm(&config.persistence, Some(offered)).await?;
+    config.chain.set_hash_algorithm(offered);
+    println!("Adopted the {offered} hash algorithm of the chain");
+
+    Ok(offered)
+}
@@ -0,0 +1,114 @@
+use anyhow::{bail, Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use crate::persistence::models::BlockData;
+
+/// ed25519 key a node signs the blocks it seals with,
+/// stored as the hex encoded 32 byte secret key
+pub struct NodeKey {
+    signing_key: SigningKey,
+}
+
+impl NodeKey {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read key file {}", path.display()))?;
+
+        let mut secret = [0; 32];
+        hex::decode_to_slice(contents.trim(), &mut secret).with_context(|| {
+            format!(
+                "key file {} isn't a hex encoded 32 byte key",
+                path.display()
+            )
+        })?;
+
+        Ok(Self {
+            signing_key: SigningKey::from_bytes(&secret),
+        })
+    }
+
+    /// Loads the key at `path`, creating it first if there's none
+    pub fn load_or_generate(path: &Path) -> Result<Self> {
+        if path.exists() {
+            return Self::load(path);
+        }
+
+        println!("Generating node key at {}...", path.display());
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        Self::generate(path)
+    }
+
+    /// Creates a key and writes it to `path`, which must not exist yet
+    pub fn generate(path: &Path) -> Result<Self> {
+        let key = Self {
+            signing_key: SigningKey::from_bytes(&rand::random()),
+        };
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // only the node reads its key
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options
+            .open(path)
+            .with_context(|| format!("failed to create key file {}", path.display()))?;
+        writeln!(file, "{}", hex::encode(key.signing_key.to_bytes()))?;
+
+        Ok(key)
+    }
+
+    /// Hex encoded public key, as listed in `producers`
+    pub fn public_key(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().as_bytes())
+    }
+
+    /// Signs the hash of `block` as its producer
+    pub fn sign(&self, block: &mut BlockData) {
+        let signature = self.signing_key.sign(block.hash.as_bytes());
+        block.producer = Some(self.public_key());
+        block.signature = Some(hex::encode(signature.to_bytes()));
+    }
+}
+
+#[cfg(test)]
+impl NodeKey {
+    /// Key from a fixed secret, without a key file
+    pub fn from_secret(secret: [u8; 32]) -> Self {
+        Self {
+            signing_key: SigningKey::from_bytes(&secret),
+        }
+    }
+}
+
+/// Parses a hex encoded ed25519 public key
+pub fn parse_public_key(key: &str) -> Result<VerifyingKey> {
+    let mut bytes = [0; 32];
+    if hex::decode_to_slice(key, &mut bytes).is_err() {
+        bail!("{key} isn't a hex encoded 32 byte public key");
+    }
+    VerifyingKey::from_bytes(&bytes).with_context(|| format!("{key} isn't an ed25519 public key"))
+}
+
+/// Checks the signature of `block` by its producer,
+/// the error is the reason it doesn't hold
+pub fn check_signature(block: &BlockData) -> Result<(), String> {
+    let (Some(producer), Some(signature)) = (&block.producer, &block.signature) else {
+        return Err(String::from("block isn't signed"));
+    };
+
+    let producer = parse_public_key(producer).map_err(|err| err.to_string())?;
+    let mut bytes = [0; 64];
+    if hex::decode_to_slice(signature, &mut bytes).is_err() {
+        return Err(format!("signature {signature} isn't hex encoded 64 bytes"));
+    }
+
+    producer
+        .verify(block.hash.as_bytes(), &Signature::from_bytes(&bytes))
+        .map_err(|_| format!("signature {signature} doesn't match the block hash"))
+}
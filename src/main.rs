@@ -1,85 +1,82 @@
-use anyhow::Result;
-use axum::Router;
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
 
+mod authority;
+mod backup;
 mod blockchain;
+mod chain_file;
 mod cli;
+mod command;
 mod config;
+mod encoding;
 mod error;
+mod follower;
+mod forwarder;
+mod hasher;
+mod keys;
+mod mempool;
 mod persistence;
+mod replication;
 mod router;
 mod service;
+mod snapshot;
 mod utils;
+mod verifier;
 
-use blockchain::{Chain, Node};
+use chain_file::ChainFormat;
 use cli::Cli;
-use config::{Configuration, Mode};
-use router::{api, ui};
+use config::Settings;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // load environment variables (a .env file is optional)
+    dotenvy::dotenv().ok();
+
     // get CLI matches
     let matches = Cli::get_matches();
 
-    // parse arguments and flags
-    let port = *matches.get_one::<u32>("PORT").unwrap();
-    let block_time = *matches.get_one::<u32>("BLOCKTIME").unwrap();
-    let mode = matches.get_one::<Mode>("MODE").unwrap();
-
-    // store in config struct
-    let shared_config = Arc::new(Configuration {
-        port: port.to_owned(),
-        block_time: block_time.to_owned(),
-        mode: mode.to_owned(),
-        chain: Chain::new(),
-    });
-
-    // display config with beautiful table
-    utils::display_configuration(&port, &block_time, mode);
-
-    // run the Chain in a task with Node runner
-    let config = Arc::clone(&shared_config);
-    let chain_handle = tokio::spawn(async move {
-        println!("Spawning node runner...");
-
-        // get ownership of BLOCK TIME
-        let owned_block_time = block_time.to_owned();
-
-        // run the node
-        let config = config;
-        let node = Node::new().await?;
-
-        // create genesis block
-        node.store_genesis_block().await?;
-
-        node.run(&config.chain, owned_block_time).await
-    });
-
-    // get routes and merge under one App route
-    let app = Router::new()
-        // route /assets (serve static files from /assets)
-        .merge(service::service()?)
-        // route /api/
-        .merge(api::router(shared_config.clone())?)
-        // route / (for rendering templates)
-        .merge(ui::router()?);
-
-    // add global 404 page
-    let app = app.fallback(ui::not_found);
-
-    // serve block factory in a task
-    let server_handle = tokio::spawn(async move {
-        println!("Spawning server...");
-        // get ownership of PORT
-        let owned_port = port.to_owned();
-        // run our app with hyper, listening globally on {--port}
-        let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{owned_port}")).await?;
-        // run the server
-        axum::serve(listener, app).await
-    });
-
-    chain_handle.await??;
-    server_handle.await??;
-
-    Ok(())
+    // flags may come before or after the (nested) subcommand
+    let mut flag_matches = vec![&matches];
+    while let Some((_, sub_matches)) = flag_matches[flag_matches.len() - 1].subcommand() {
+        flag_matches.push(sub_matches);
+    }
+
+    // layer configuration file, environment variables and flags
+    let settings = Settings::load(&flag_matches).context("invalid configuration")?;
+
+    match matches.subcommand() {
+        Some(("verify", _)) => command::verify::run(&settings).await,
+        Some(("export", sub_matches)) => {
+            let format = *sub_matches.get_one::<ChainFormat>("FORMAT").unwrap();
+            let output = sub_matches.get_one::<PathBuf>("OUTPUT");
+            command::export::run(&settings, format, output).await
+        }
+        Some(("import", sub_matches)) => {
+            let input = sub_matches.get_one::<PathBuf>("INPUT").unwrap();
+            command::import::run(&settings, input).await
+        }
+        Some(("backup", _)) => command::backup::run(&settings).await,
+        Some(("restore", sub_matches)) => {
+            let backup = sub_matches.get_one::<PathBuf>("BACKUP").unwrap();
+            command::restore::run(&settings, backup).await
+        }
+        Some(("inspect", sub_matches)) => match sub_matches.subcommand() {
+            Some(("block", block_matches)) => {
+                let block = block_matches.get_one::<String>("BLOCK").unwrap();
+                command::inspect::block(&settings, block).await
+            }
+            Some(("tx", tx_matches)) => {
+                let hash = tx_matches.get_one::<String>("HASH").unwrap();
+                command::inspect::transaction(&settings, hash).await
+            }
+            _ => unreachable!("inspect requires a subcommand"),
+        },
+        Some(("stats", _)) => command::stats::run(&settings).await,
+        Some(("keygen", sub_matches)) => {
+            let output = sub_matches.get_one::<PathBuf>("OUTPUT").unwrap();
+            command::keygen::run(output)
+        }
+        // `serve`, or no subcommand at all
+        _ => command::serve::run(settings).await,
+    }
 }
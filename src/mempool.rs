@@ -0,0 +1,267 @@
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::blockchain::{BlockLimits, PendingTransaction};
+
+/// Ordering key of a pooled [`PendingTransaction`],
+/// highest fee first and arrival order on ties
+type PoolKey = (Reverse<u32>, u64);
+
+// rejected transactions remembered for status lookups
+const REJECTED_CAPACITY: usize = 1024;
+
+/// A [`PendingTransaction`] along with its position in the pool
+#[derive(Debug, Serialize)]
+pub struct PooledTransaction<'a> {
+    pub position: usize,
+    pub transaction: &'a PendingTransaction,
+}
+
+/// Priority queue of transactions waiting to be sealed
+#[derive(Debug, Default)]
+pub struct TransactionPool {
+    entries: BTreeMap<PoolKey, PendingTransaction>,
+    // transaction hash to its key in entries
+    keys: HashMap<String, PoolKey>,
+    // arrival counter to keep ordering deterministic on equal fees
+    next_sequence: u64,
+}
+
+impl TransactionPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds a [`PendingTransaction`] to the pool
+    pub fn push(&mut self, tx: PendingTransaction) {
+        let key = (Reverse(tx.transaction.fee), self.next_sequence);
+        self.next_sequence += 1;
+        self.keys.insert(tx.hash.clone(), key);
+        self.entries.insert(key, tx);
+    }
+
+    /// Returns the pooled transaction with `hash`
+    pub fn get(&self, hash: &str) -> Option<&PendingTransaction> {
+        let key = self.keys.get(hash)?;
+        self.entries.get(key)
+    }
+
+    /// Returns the place in the sealing order of the transaction with `hash`
+    pub fn position(&self, hash: &str) -> Option<usize> {
+        let key = self.keys.get(hash)?;
+        Some(self.entries.range(..key).count())
+    }
+
+    /// Returns the pooled transactions in the order they will be sealed
+    pub fn ordered(&self) -> Vec<PooledTransaction<'_>> {
+        self.entries
+            .values()
+            .enumerate()
+            .map(|(position, transaction)| PooledTransaction {
+                position,
+                transaction,
+            })
+            .collect()
+    }
+
+    /// Removes the highest priority transactions that fit
+    /// within the [`BlockLimits`], the rest stays in the pool
+    pub fn drain_block(&mut self, limits: &BlockLimits) -> Vec<PendingTransaction> {
+        let mut transactions = vec![];
+        let mut bytes = 0;
+
+        while let Some(entry) = self.entries.first_entry() {
+            if matches!(limits.max_transactions, Some(max) if transactions.len() >= max) {
+                break;
+            }
+            // don't skip ahead of a transaction that doesn't fit, it goes first next block
+            let size = entry.get().transaction.size();
+            if matches!(limits.max_bytes, Some(max) if bytes + size > max) {
+                break;
+            }
+            bytes += size;
+            let tx = entry.remove();
+            self.keys.remove(&tx.hash);
+            transactions.push(tx);
+        }
+
+        transactions
+    }
+}
+
+/// Bounded record of refused transactions, oldest are forgotten first
+#[derive(Debug, Default)]
+pub struct RejectedTransactions {
+    reasons: HashMap<String, String>,
+    // insertion order of hashes, for eviction
+    order: VecDeque<String>,
+}
+
+impl RejectedTransactions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers the rejection `reason` of the transaction with `hash`
+    pub fn insert(&mut self, hash: String, reason: String) {
+        if self.order.len() >= REJECTED_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.reasons.remove(&oldest);
+            }
+        }
+        self.order.push_back(hash.clone());
+        self.reasons.insert(hash, reason);
+    }
+
+    pub fn reason(&self, hash: &str) -> Option<&str> {
+        self.reasons.get(hash).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Transaction;
+    use crate::hasher::HashAlgorithm;
+
+    // `size` is the bytes the transaction counts for in a block
+    fn pending(name: &str, fee: u32, size: usize) -> PendingTransaction {
+        let instruction = vec![b'x'; size - name.len() - "to".len()];
+        let tx = Transaction {
+            from: name.to_owned(),
+            to: String::from("to"),
+            instruction,
+            fee,
+        };
+        PendingTransaction::new(tx, HashAlgorithm::Sha256)
+    }
+
+    fn pool_of(transactions: &[PendingTransaction]) -> TransactionPool {
+        let mut pool = TransactionPool::new();
+        for tx in transactions {
+            pool.push(tx.clone());
+        }
+        pool
+    }
+
+    fn senders(transactions: &[PendingTransaction]) -> Vec<&str> {
+        transactions
+            .iter()
+            .map(|tx| tx.transaction.from.as_str())
+            .collect()
+    }
+
+    fn unlimited() -> BlockLimits {
+        BlockLimits {
+            max_transactions: None,
+            max_bytes: None,
+            max_blocks_per_epoch: 1,
+        }
+    }
+
+    #[test]
+    fn seals_highest_fee_first() {
+        let mut pool = pool_of(&[
+            pending("a", 1, 10),
+            pending("b", 5, 10),
+            pending("c", 3, 10),
+        ]);
+
+        let ordered: Vec<&str> = pool
+            .ordered()
+            .iter()
+            .map(|pooled| pooled.transaction.transaction.from.as_str())
+            .collect();
+        assert_eq!(ordered, ["b", "c", "a"]);
+        assert_eq!(senders(&pool.drain_block(&unlimited())), ["b", "c", "a"]);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn keeps_arrival_order_on_equal_fees() {
+        // fresh ids and hashes every time, the order only follows arrival
+        for _ in 0..5 {
+            let mut pool = pool_of(&[
+                pending("a", 2, 10),
+                pending("b", 2, 10),
+                pending("c", 7, 10),
+                pending("d", 2, 10),
+            ]);
+            assert_eq!(
+                senders(&pool.drain_block(&unlimited())),
+                ["c", "a", "b", "d"]
+            );
+        }
+    }
+
+    #[test]
+    fn positions_follow_the_sealing_order() {
+        let transactions = [
+            pending("a", 1, 10),
+            pending("b", 5, 10),
+            pending("c", 3, 10),
+        ];
+        let mut pool = pool_of(&transactions);
+
+        assert_eq!(pool.position(&transactions[1].hash), Some(0));
+        assert_eq!(pool.position(&transactions[2].hash), Some(1));
+        assert_eq!(pool.position(&transactions[0].hash), Some(2));
+        assert_eq!(pool.position("unknown"), None);
+
+        // the rest moves up once the first is sealed
+        let limits = BlockLimits {
+            max_transactions: Some(1),
+            ..unlimited()
+        };
+        pool.drain_block(&limits);
+        assert_eq!(pool.position(&transactions[1].hash), None);
+        assert_eq!(pool.position(&transactions[2].hash), Some(0));
+        assert_eq!(pool.position(&transactions[0].hash), Some(1));
+        assert!(pool.get(&transactions[1].hash).is_none());
+    }
+
+    #[test]
+    fn drains_up_to_the_transaction_limit() {
+        let mut pool = pool_of(&[
+            pending("a", 3, 10),
+            pending("b", 2, 10),
+            pending("c", 1, 10),
+        ]);
+        let limits = BlockLimits {
+            max_transactions: Some(2),
+            ..unlimited()
+        };
+
+        assert_eq!(senders(&pool.drain_block(&limits)), ["a", "b"]);
+        assert_eq!(senders(&pool.drain_block(&limits)), ["c"]);
+        assert!(pool.drain_block(&limits).is_empty());
+    }
+
+    #[test]
+    fn drains_up_to_the_byte_limit_without_skipping_ahead() {
+        let mut pool = pool_of(&[
+            pending("a", 4, 40),
+            pending("b", 3, 50),
+            pending("c", 2, 60),
+            // would still fit after b, but must wait for c
+            pending("d", 1, 10),
+        ]);
+        let limits = BlockLimits {
+            max_bytes: Some(100),
+            ..unlimited()
+        };
+
+        assert_eq!(senders(&pool.drain_block(&limits)), ["a", "b"]);
+        assert_eq!(senders(&pool.drain_block(&limits)), ["c", "d"]);
+        assert!(pool.is_empty());
+    }
+}
@@ -3,3 +3,4 @@ pub mod port;
 pub use port::*;
 pub mod models;
 pub mod sqlite;
+pub mod walker;
@@ -1,58 +1,168 @@
-use crate::blockchain::Transaction;
-use anyhow::Result;
-use sha256::digest;
+use crate::blockchain::PendingTransaction;
+use crate::encoding::{self, BLOCK_HEADER_VERSION, LEGACY_HEADER_VERSION};
+use crate::hasher::HashAlgorithm;
+use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use std::str;
 use uuid::Uuid;
 
+/// Which side of a transaction an address is on
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Sent or received
+    #[default]
+    All,
+    /// `from` the address
+    Sent,
+    /// `to` the address
+    Received,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TransactionData {
     // uuidv7 with timestamp
     pub id: String,
     pub hash: String,
     pub from: String,
     pub to: String,
+    #[serde(with = "serde_bytes")]
     pub instruction: Vec<u8>,
+    pub fee: u32,
+    // height of the block it was sealed in
+    pub block_height: u32,
+    // index within the block
+    pub position: u32,
 }
 
-impl TryFrom<Transaction> for TransactionData {
-    type Error = anyhow::Error;
-
-    fn try_from(tx: Transaction) -> Result<Self> {
-        let id = Uuid::now_v7().to_string();
-        let from = tx.from;
-        let to = tx.to;
-        let instruction = tx.instruction.clone();
-        let instr_string = str::from_utf8(&tx.instruction[..])?.to_string();
-        // generate hash
-        let hash = digest(format!("{id}{from}{to}{instr_string}"));
-
-        Ok(TransactionData {
-            hash,
-            id,
-            from,
-            to,
-            instruction,
-        })
+impl TransactionData {
+    /// Creates the [`TransactionData`] of a [`PendingTransaction`]
+    /// sealed at `position` in the block at `block_height`
+    pub fn from_sealed(pending: PendingTransaction, block_height: u32, position: u32) -> Self {
+        let tx = pending.transaction;
+
+        TransactionData {
+            hash: pending.hash,
+            id: pending.id,
+            from: tx.from,
+            to: tx.to,
+            instruction: tx.instruction,
+            fee: tx.fee,
+            block_height,
+            position,
+        }
+    }
+
+    /// Hash of a transaction sealed in a block with a current header
+    pub fn get_hash(
+        algorithm: HashAlgorithm,
+        id: &str,
+        from: &str,
+        to: &str,
+        instruction: &[u8],
+        fee: u32,
+    ) -> String {
+        algorithm.digest(&encoding::transaction(id, from, to, instruction, fee))
+    }
+
+    /// Recomputes the hash from the stored fields with the chain's `algorithm`,
+    /// encoded as in blocks of `header_version`, `None` if it can't be
+    pub fn computed_hash(&self, algorithm: HashAlgorithm, header_version: u32) -> Option<String> {
+        let (id, from, to, fee) = (&self.id, &self.from, &self.to, self.fee);
+        let encoded = match header_version {
+            BLOCK_HEADER_VERSION => encoding::transaction(id, from, to, &self.instruction, fee),
+            LEGACY_HEADER_VERSION => {
+                encoding::legacy_transaction(id, from, to, &self.instruction, fee)?
+            }
+            _ => return None,
+        };
+        Some(algorithm.digest(&encoded))
+    }
+
+    /// Instruction as text, invalid UTF-8 is replaced
+    pub fn instruction_text(&self) -> String {
+        String::from_utf8_lossy(&self.instruction).into_owned()
     }
 }
 
-#[derive(Debug, FromRow)]
+#[derive(Clone, Debug, Deserialize, FromRow, Serialize)]
 pub struct BlockData {
+    // header format the hash covers, blocks from before it was recorded are legacy
+    #[serde(default = "legacy_header_version")]
+    pub version: u32,
     // uuidv7 with timestamp
     pub id: String,
     pub hash: String,
     pub nonce: u32,
     pub height: u32,
     pub prev_block_hash: String,
+    // hex ed25519 public key of the node that sealed it, none if unsigned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub producer: Option<String>,
+    // hex ed25519 signature of `hash` by `producer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 impl BlockData {
-    pub fn get_sha256_hash(
-        id: &String,
-        nonce: &u32,
-        height: &u32,
-        prev_block_hash: &String,
+    /// Hash of a header in the current format, over the hashes
+    /// of the block's transactions in block order
+    pub fn get_hash(
+        algorithm: HashAlgorithm,
+        id: &str,
+        nonce: u32,
+        height: u32,
+        prev_block_hash: &str,
+        transaction_hashes: &[&str],
     ) -> String {
-        digest(format!("{}{}{}{}", *id, *nonce, *height, *prev_block_hash))
+        let encoded =
+            encoding::block_header(id, nonce, height, prev_block_hash, transaction_hashes);
+        algorithm.digest(&encoded)
+    }
+
+    /// Recomputes the hash from the stored fields and the hashes of its
+    /// `transactions` (in block order) with the chain's `algorithm`,
+    /// in the header format of its `version`, `None` for unknown versions
+    pub fn computed_hash(
+        &self,
+        algorithm: HashAlgorithm,
+        transactions: &[TransactionData],
+    ) -> Option<String> {
+        let (id, nonce, height, prev) = (&self.id, self.nonce, self.height, &self.prev_block_hash);
+        let encoded = match self.version {
+            BLOCK_HEADER_VERSION => {
+                let hashes: Vec<&str> = transactions.iter().map(|tx| tx.hash.as_str()).collect();
+                encoding::block_header(id, nonce, height, prev, &hashes)
+            }
+            LEGACY_HEADER_VERSION => encoding::legacy_block_header(id, nonce, height, prev),
+            _ => return None,
+        };
+        Some(algorithm.digest(&encoded))
+    }
+
+    /// Creation time of the block, read from its uuidv7 id
+    pub fn created_at(&self) -> String {
+        Uuid::parse_str(&self.id)
+            .ok()
+            .and_then(|id| id.get_timestamp())
+            .and_then(|timestamp| {
+                let (secs, nanos) = timestamp.to_unix();
+                chrono::DateTime::from_timestamp(secs as i64, nanos)
+            })
+            .map(|created_at| created_at.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_default()
     }
 }
+
+fn legacy_header_version() -> u32 {
+    LEGACY_HEADER_VERSION
+}
+
+/// Totals over the whole stored chain
+#[derive(Clone, Debug, Serialize)]
+pub struct ChainStats {
+    pub blocks: u32,
+    pub transactions: u32,
+    pub total_fees: u64,
+    // distinct senders and receivers
+    pub addresses: u32,
+}
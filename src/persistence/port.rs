@@ -1,12 +1,53 @@
-use super::models::{BlockData, TransactionData};
+use super::models::{BlockData, ChainStats, Direction, TransactionData};
 use anyhow::Result;
 
 /// Trait for handling Node persistency
 pub trait NodePersistency {
-    /// Store [`BlockData`] into local DB
+    /// Read the [`BlockData`] of the tip, the highest stored block
     async fn read_latest_block_data(&self) -> Result<BlockData>;
-    /// Store [`BlockData`] into local DB
-    async fn store_block_data(&self, block_data: BlockData) -> Result<()>;
-    /// Store [`TransactionData`] into local DB
-    async fn store_transaction_data(&self, tx_data: TransactionData) -> Result<()>;
+    /// Read up to `limit` [`BlockData`] up to `max_height` skipping `offset`, highest first
+    async fn read_recent_block_data(
+        &self,
+        max_height: u32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<BlockData>>;
+    /// Read up to `limit` [`BlockData`] from `height` upwards, lowest first
+    async fn read_block_data_from_height(&self, height: u32, limit: u32) -> Result<Vec<BlockData>>;
+    /// Read the [`BlockData`] at `height`
+    async fn read_block_data_by_height(&self, height: u32) -> Result<Option<BlockData>>;
+    /// Read the [`BlockData`] with `hash`
+    async fn read_block_data_by_hash(&self, hash: &str) -> Result<Option<BlockData>>;
+    /// Read the [`TransactionData`] with `hash`
+    async fn read_transaction_data_by_hash(&self, hash: &str) -> Result<Option<TransactionData>>;
+    /// Read the [`TransactionData`] of the block at `block_height`, in block order
+    async fn read_block_transaction_data(&self, block_height: u32) -> Result<Vec<TransactionData>>;
+    /// Read up to `limit` [`TransactionData`] of `address` in `direction`
+    /// sealed up to `max_height`, newest first
+    async fn read_address_transaction_data(
+        &self,
+        address: &str,
+        direction: Direction,
+        max_height: u32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<TransactionData>>;
+    /// Count the transactions sent and received by `address` up to `max_height`
+    async fn count_address_transaction_data(
+        &self,
+        address: &str,
+        max_height: u32,
+    ) -> Result<(u32, u32)>;
+    /// Count blocks, transactions, fees and addresses of the whole chain
+    async fn read_chain_stats(&self) -> Result<ChainStats>;
+    /// Read the chain metadata value under `key`
+    async fn read_chain_metadata(&self, key: &str) -> Result<Option<String>>;
+    /// Store a chain metadata value under `key`, replacing the previous one
+    async fn store_chain_metadata(&self, key: &str, value: &str) -> Result<()>;
+    /// Store [`BlockData`] with its [`TransactionData`] into local DB, all or nothing
+    async fn store_sealed_block_data(
+        &self,
+        block_data: &BlockData,
+        transactions: &[TransactionData],
+    ) -> Result<()>;
 }
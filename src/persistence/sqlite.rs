@@ -1,80 +1,335 @@
-use super::models::{BlockData, TransactionData};
+use super::models::{BlockData, ChainStats, Direction, TransactionData};
 use super::NodePersistency;
 use crate::error::BlockFactoryError;
 use anyhow::{bail, Result};
-use sqlx::{Pool, Row, Sqlite, SqlitePool};
-use std::env;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteRow},
+    Pool, Row, Sqlite, SqlitePool,
+};
+use std::path::Path;
+use std::str::FromStr;
 
+#[derive(Clone)]
 pub struct SqlitePersistence {
     // only SQLite for now
     pub pool: Pool<Sqlite>,
 }
 
 impl SqlitePersistence {
-    pub async fn from_env() -> Result<Self> {
-        // load environmentt variables
-        dotenvy::dotenv()?;
-
-        let db_url = env::var("DATABASE_URL")?;
-
+    pub async fn connect(db_url: &str) -> Result<Self> {
         // [kristian] TODO: configure optimal connection pooling options
         // create a connection pool
         // only SQLite for now
-        let pool = SqlitePool::connect(db_url.as_str()).await?;
+        // WAL lets readers (explorer, backups) run while blocks are stored
+        let options = SqliteConnectOptions::from_str(db_url)?.journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePool::connect_with(options).await?;
         // auto migrate tables
         sqlx::migrate!("./migrations").run(&pool).await?;
 
         Ok(Self { pool })
     }
+
+    /// Connects to the database at `db_url` without migrating or writing to it,
+    /// for nodes and commands that only read what another node stores
+    pub async fn connect_read_only(db_url: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(db_url)?
+            .create_if_missing(false)
+            .read_only(true);
+        let pool = SqlitePool::connect_with(options).await?;
+
+        let persistence = Self { pool };
+        persistence.check_schema().await?;
+        Ok(persistence)
+    }
+
+    /// Opens the database file at `path` without migrating or writing to it
+    pub async fn open_read_only(path: &Path) -> Result<Self> {
+        let options = SqliteConnectOptions::new().filename(path).read_only(true);
+        let pool = SqlitePool::connect_with(options).await?;
+
+        Ok(Self { pool })
+    }
+
+    // read-only connections can't migrate, an older schema lacks columns
+    async fn check_schema(&self) -> Result<()> {
+        let latest = sqlx::migrate!("./migrations")
+            .iter()
+            .map(|migration| migration.version)
+            .max();
+        let applied: Option<i64> =
+            sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+                .fetch_one(&self.pool)
+                .await
+                .unwrap_or_default();
+
+        if applied < latest {
+            bail!("database schema is out of date, start a node on it once to migrate it");
+        }
+        Ok(())
+    }
+
+    /// Writes a consistent copy of the database to `path`,
+    /// blocks keep being stored while it runs
+    pub async fn vacuum_into(&self, path: &Path) -> Result<()> {
+        let Some(path) = path.to_str() else {
+            bail!("backup path {} isn't valid UTF-8", path.display());
+        };
+
+        sqlx::query("VACUUM INTO $1")
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
 }
 
 impl NodePersistency for SqlitePersistence {
     async fn read_latest_block_data(&self) -> Result<BlockData> {
         let option_row = sqlx::query(
-            "SELECT id, hash, height, prev_block_hash, nonce FROM block_data ORDER BY id DESC",
+            "SELECT id, hash, height, prev_block_hash, nonce, producer, signature, version FROM block_data ORDER BY height DESC LIMIT 1",
         )
         .fetch_optional(&self.pool)
         .await?;
 
         match option_row {
-            Some(record) => Ok(BlockData {
-                id: record.get(0),
-                hash: record.get(1),
-                height: record.get(2),
-                prev_block_hash: record.get(3),
-                nonce: record.get(4),
-            }),
+            Some(record) => Ok(block_data_from_row(&record)),
 
             None => bail!(BlockFactoryError::MissingGenesis),
         }
     }
 
-    async fn store_block_data(&self, block_data: BlockData) -> Result<()> {
-        sqlx::query( "INSERT INTO block_data (id, hash, height, prev_block_hash, nonce) VALUES ($1, $2, $3, $4, $5)",
+    async fn read_recent_block_data(
+        &self,
+        max_height: u32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<BlockData>> {
+        let rows = sqlx::query(
+            "SELECT id, hash, height, prev_block_hash, nonce, producer, signature, version FROM block_data WHERE height <= $1 ORDER BY height DESC LIMIT $2 OFFSET $3",
         )
-        .bind(block_data.id)
-        .bind(block_data.hash)
-        .bind(block_data.height)
-        .bind(block_data.prev_block_hash)
-        .bind(block_data.nonce)
+        .bind(max_height)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(block_data_from_row).collect())
+    }
+
+    async fn read_block_data_from_height(&self, height: u32, limit: u32) -> Result<Vec<BlockData>> {
+        let rows = sqlx::query(
+            "SELECT id, hash, height, prev_block_hash, nonce, producer, signature, version FROM block_data WHERE height >= $1 ORDER BY height ASC LIMIT $2",
+        )
+        .bind(height)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(block_data_from_row).collect())
+    }
+
+    async fn read_block_data_by_height(&self, height: u32) -> Result<Option<BlockData>> {
+        let option_row = sqlx::query(
+            "SELECT id, hash, height, prev_block_hash, nonce, producer, signature, version FROM block_data WHERE height = $1",
+        )
+        .bind(height)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(option_row.as_ref().map(block_data_from_row))
+    }
+
+    async fn read_block_data_by_hash(&self, hash: &str) -> Result<Option<BlockData>> {
+        let option_row = sqlx::query(
+            "SELECT id, hash, height, prev_block_hash, nonce, producer, signature, version FROM block_data WHERE hash = $1",
+        )
+        .bind(hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(option_row.as_ref().map(block_data_from_row))
+    }
+
+    async fn read_transaction_data_by_hash(&self, hash: &str) -> Result<Option<TransactionData>> {
+        let option_row = sqlx::query(
+            "SELECT id, hash, from_address, to_address, instruction, fee, block_height, position FROM transaction_data WHERE hash = $1",
+        )
+        .bind(hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(option_row.as_ref().map(transaction_data_from_row))
+    }
+
+    async fn read_block_transaction_data(&self, block_height: u32) -> Result<Vec<TransactionData>> {
+        let rows = sqlx::query(
+            "SELECT id, hash, from_address, to_address, instruction, fee, block_height, position FROM transaction_data WHERE block_height = $1 ORDER BY position ASC",
+        )
+        .bind(block_height)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(transaction_data_from_row).collect())
+    }
+
+    async fn read_address_transaction_data(
+        &self,
+        address: &str,
+        direction: Direction,
+        max_height: u32,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<TransactionData>> {
+        let filter = match direction {
+            Direction::All => "from_address = $1 OR to_address = $1",
+            Direction::Sent => "from_address = $1",
+            Direction::Received => "to_address = $1",
+        };
+
+        let rows = sqlx::query(&format!(
+            "SELECT id, hash, from_address, to_address, instruction, fee, block_height, position FROM transaction_data WHERE ({filter}) AND block_height <= $2 ORDER BY block_height DESC, position DESC LIMIT $3 OFFSET $4",
+        ))
+        .bind(address)
+        .bind(max_height)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(transaction_data_from_row).collect())
+    }
+
+    async fn count_address_transaction_data(
+        &self,
+        address: &str,
+        max_height: u32,
+    ) -> Result<(u32, u32)> {
+        let record = sqlx::query(
+            "SELECT (SELECT COUNT(*) FROM transaction_data WHERE from_address = $1 AND block_height <= $2), (SELECT COUNT(*) FROM transaction_data WHERE to_address = $1 AND block_height <= $2)",
+        )
+        .bind(address)
+        .bind(max_height)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok((record.get(0), record.get(1)))
+    }
+
+    async fn read_chain_stats(&self) -> Result<ChainStats> {
+        let record = sqlx::query(
+            "SELECT (SELECT COUNT(*) FROM block_data), (SELECT COUNT(*) FROM transaction_data), (SELECT COALESCE(SUM(fee), 0) FROM transaction_data), (SELECT COUNT(*) FROM (SELECT from_address FROM transaction_data UNION SELECT to_address FROM transaction_data))",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ChainStats {
+            blocks: record.get(0),
+            transactions: record.get(1),
+            total_fees: record.get::<i64, _>(2) as u64,
+            addresses: record.get(3),
+        })
+    }
+
+    async fn read_chain_metadata(&self, key: &str) -> Result<Option<String>> {
+        // backups taken before chain metadata are opened without migrating
+        let has_metadata: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'chain_metadata')",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if !has_metadata {
+            return Ok(None);
+        }
+
+        let option_row = sqlx::query("SELECT value FROM chain_metadata WHERE key = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(option_row.map(|record| record.get(0)))
+    }
+
+    async fn store_chain_metadata(&self, key: &str, value: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO chain_metadata (key, value) VALUES ($1, $2) ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        )
+        .bind(key)
+        .bind(value)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    async fn store_transaction_data(&self, tx_data: TransactionData) -> Result<()> {
+    async fn store_sealed_block_data(
+        &self,
+        block_data: &BlockData,
+        transactions: &[TransactionData],
+    ) -> Result<()> {
+        // one SQL transaction, readers never see a block without its transactions
+        let mut db_tx = self.pool.begin().await?;
+
         sqlx::query(
-            "INSERT INTO transaction_data (id, hash, from_address, to_address, instruction) VALUES ($1, $2, $3, $4, $5)",
+            "INSERT INTO block_data (id, hash, height, prev_block_hash, nonce, producer, signature, version) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
         )
-        .bind(tx_data.id)
-        .bind(tx_data.hash)
-        .bind(tx_data.from)
-        .bind(tx_data.to)
-        .bind(tx_data.instruction)
-        .execute(&self.pool)
+        .bind(&block_data.id)
+        .bind(&block_data.hash)
+        .bind(block_data.height)
+        .bind(&block_data.prev_block_hash)
+        .bind(block_data.nonce)
+        .bind(&block_data.producer)
+        .bind(&block_data.signature)
+        .bind(block_data.version)
+        .execute(&mut *db_tx)
         .await?;
 
+        for tx_data in transactions {
+            sqlx::query(
+                "INSERT INTO transaction_data (id, hash, from_address, to_address, instruction, fee, block_height, position) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            )
+            .bind(&tx_data.id)
+            .bind(&tx_data.hash)
+            .bind(&tx_data.from)
+            .bind(&tx_data.to)
+            .bind(&tx_data.instruction)
+            .bind(tx_data.fee)
+            .bind(tx_data.block_height)
+            .bind(tx_data.position)
+            .execute(&mut *db_tx)
+            .await?;
+        }
+
+        db_tx.commit().await?;
+
         Ok(())
     }
 }
+
+// expects columns: id, hash, height, prev_block_hash, nonce, producer, signature, version
+fn block_data_from_row(record: &SqliteRow) -> BlockData {
+    BlockData {
+        id: record.get(0),
+        hash: record.get(1),
+        height: record.get(2),
+        prev_block_hash: record.get(3),
+        nonce: record.get(4),
+        producer: record.get(5),
+        signature: record.get(6),
+        version: record.get(7),
+    }
+}
+
+// expects columns: id, hash, from_address, to_address, instruction, fee, block_height, position
+fn transaction_data_from_row(record: &SqliteRow) -> TransactionData {
+    TransactionData {
+        id: record.get(0),
+        hash: record.get(1),
+        from: record.get(2),
+        to: record.get(3),
+        instruction: record.get(4),
+        fee: record.get(5),
+        block_height: record.get(6),
+        position: record.get(7),
+    }
+}
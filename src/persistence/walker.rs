@@ -0,0 +1,58 @@
+use anyhow::Result;
+use std::collections::VecDeque;
+
+use super::{models::BlockData, sqlite::SqlitePersistence, NodePersistency};
+use crate::blockchain::SealedBlock;
+
+// blocks read per query while walking the chain
+const WALK_PAGE_SIZE: u32 = 256;
+
+/// Reads stored blocks with their transactions from genesis upwards,
+/// a page at a time so the chain never has to fit in memory
+pub struct ChainWalker<'a> {
+    persistence: &'a SqlitePersistence,
+    next_height: u32,
+    page: VecDeque<BlockData>,
+}
+
+impl<'a> ChainWalker<'a> {
+    pub fn new(persistence: &'a SqlitePersistence) -> Self {
+        // genesis is at height 1
+        Self::from_height(persistence, 1)
+    }
+
+    /// Starts walking at `height` instead of genesis
+    pub fn from_height(persistence: &'a SqlitePersistence, height: u32) -> Self {
+        Self {
+            persistence,
+            next_height: height,
+            page: VecDeque::new(),
+        }
+    }
+
+    /// Returns the next [`SealedBlock`], `None` past the tip
+    pub async fn next(&mut self) -> Result<Option<SealedBlock>> {
+        if self.page.is_empty() {
+            self.page = self
+                .persistence
+                .read_block_data_from_height(self.next_height, WALK_PAGE_SIZE)
+                .await?
+                .into();
+        }
+
+        let Some(block) = self.page.pop_front() else {
+            return Ok(None);
+        };
+        self.next_height = block.height + 1;
+
+        let transactions = self
+            .persistence
+            .read_block_transaction_data(block.height)
+            .await?;
+
+        Ok(Some(SealedBlock {
+            block,
+            transactions,
+        }))
+    }
+}
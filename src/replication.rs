@@ -0,0 +1,138 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time;
+
+use crate::blockchain::SealedBlock;
+use crate::config::Configuration;
+use crate::hasher::{adopt_hash_algorithm, HashAlgorithm};
+use crate::persistence::NodePersistency;
+use crate::verifier::ChainVerifier;
+
+/// Blocks asked for per `GET /api/blocks`, the other node caps it as well
+pub const REPLICATION_PAGE_SIZE: u32 = 100;
+// wait between requests once caught up with the primary
+const REPLICATION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+// longest a request to the primary may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// longest wait between retries while the primary is unreachable
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+struct BlocksResponse {
+    data: BlocksPage,
+}
+
+/// A page of blocks from another node's `GET /api/blocks`
+#[derive(Deserialize)]
+pub struct BlocksPage {
+    /// Missing from nodes from before it was served,
+    /// unset on nodes that have no blocks yet
+    #[serde(default)]
+    hash_algorithm: Option<HashAlgorithm>,
+    pub blocks: Vec<SealedBlock>,
+}
+
+impl BlocksPage {
+    /// Hash algorithm of the node's chain, `None` if nothing is known
+    /// about it yet (an empty page from a node without one)
+    pub fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        match self.hash_algorithm {
+            Some(algorithm) => Some(algorithm),
+            // nodes from before it was served hash with SHA-256
+            None if !self.blocks.is_empty() => Some(HashAlgorithm::Sha256),
+            None => None,
+        }
+    }
+}
+
+/// Pulls blocks from the primary factory's API from the local tip upwards,
+/// checks their hashes and linkage, then stores and publishes them
+pub async fn replicate(config: &Configuration, primary_url: &str) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+
+    config.chain.load_snapshot(&config.persistence).await?;
+    // stored blocks were checked when they were replicated, a new chain
+    // without a hash algorithm adopts the primary's with its first blocks
+    let hash_algorithm = config.chain.hash_algorithm().unwrap_or_default();
+    let mut verifier = match config.chain.snapshot().tip() {
+        Some(tip) => ChainVerifier::resume(tip.clone(), hash_algorithm),
+        None => ChainVerifier::new(hash_algorithm),
+    }
+    .with_producers(config.producers.clone());
+
+    let mut retry_delay = REPLICATION_POLL_INTERVAL;
+    loop {
+        let from_height = verifier.tip().map_or(1, |tip| tip.height + 1);
+
+        let page = match fetch_blocks(&client, primary_url, from_height).await {
+            Ok(page) => {
+                retry_delay = REPLICATION_POLL_INTERVAL;
+                page
+            }
+            Err(err) => {
+                println!(
+                    "Failed to reach primary {primary_url}: {err:#}, retrying in {retry_delay:?}"
+                );
+                time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+                continue;
+            }
+        };
+
+        if let Some(offered) = page.hash_algorithm() {
+            let adopting = config.chain.hash_algorithm().is_none();
+            let algorithm = adopt_hash_algorithm(config, offered).await?;
+            if offered != algorithm {
+                bail!(
+                    "primary {primary_url} hashes with {offered}, the local chain with {algorithm}"
+                );
+            }
+            if adopting {
+                verifier = ChainVerifier::new(algorithm).with_producers(config.producers.clone());
+            }
+        }
+
+        let caught_up = page.blocks.len() < REPLICATION_PAGE_SIZE as usize;
+        for sealed in page.blocks {
+            // a primary with another chain can't be followed
+            verifier
+                .check(&sealed.block, &sealed.transactions)
+                .with_context(|| format!("primary {primary_url} diverged from the local chain"))?;
+            config
+                .persistence
+                .store_sealed_block_data(&sealed.block, &sealed.transactions)
+                .await?;
+            config.chain.publish_sealed_block(sealed);
+        }
+
+        if caught_up {
+            time::sleep(REPLICATION_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Reads a page of blocks with their transactions from the API
+/// of the node at `node_url`, starting at `from_height`
+pub async fn fetch_blocks(
+    client: &reqwest::Client,
+    node_url: &str,
+    from_height: u32,
+) -> Result<BlocksPage> {
+    let response = client
+        .get(format!("{node_url}/api/blocks"))
+        .query(&[
+            ("from_height", from_height),
+            ("limit", REPLICATION_PAGE_SIZE),
+        ])
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        bail!("{node_url} answered {}", response.status());
+    }
+
+    Ok(response.json::<BlocksResponse>().await?.data)
+}
@@ -0,0 +1,207 @@
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post, put},
+    Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+
+use crate::backup as chain_backup;
+use crate::config::{Configuration, Mode};
+use crate::utils::parse_block_time;
+
+#[derive(Deserialize)]
+struct BlockTimePayload {
+    // same format as `--block-time` (e.g. "250ms", "2s")
+    block_time: String,
+}
+
+pub fn router(shared_config: Arc<Configuration>) -> Result<Router> {
+    let admin_routes = Router::new()
+        // GET /api/admin/status
+        .route("/status", get(status))
+        // POST /api/admin/seal
+        .route("/seal", post(seal))
+        // POST /api/admin/pause
+        .route("/pause", post(pause))
+        // POST /api/admin/resume
+        .route("/resume", post(resume))
+        // PUT /api/admin/block-time
+        .route("/block-time", put(block_time))
+        // POST /api/admin/backup
+        .route("/backup", post(backup))
+        // every admin route needs the admin token
+        .route_layer(middleware::from_fn_with_state(
+            shared_config.clone(),
+            require_admin_token,
+        ));
+
+    let admin_group = Router::new()
+        .nest("/api/admin", admin_routes)
+        .with_state(shared_config);
+
+    Ok(admin_group)
+}
+
+//---- MIDDLEWARE -----
+
+// checks `Authorization: Bearer <ADMIN_TOKEN>`
+async fn require_admin_token(
+    State(config): State<Arc<Configuration>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(admin_token) = &config.admin_token else {
+        return error(StatusCode::FORBIDDEN, "admin API is disabled").into_response();
+    };
+
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    // constant time, the comparison mustn't reveal how much of the token matched
+    match bearer {
+        Some(token) if bool::from(token.as_bytes().ct_eq(admin_token.as_bytes())) => {
+            next.run(request).await
+        }
+        _ => error(StatusCode::UNAUTHORIZED, "invalid admin token").into_response(),
+    }
+}
+
+//---- HANDLERS -----
+
+// GET /admin/status
+async fn status(State(config): State<Arc<Configuration>>) -> Json<Value> {
+    let config = Arc::clone(&config);
+
+    // requests waiting for the primary, query-only nodes with one only
+    let forward_buffer = match &config.forwarder {
+        Some(forwarder) => Some(forwarder.buffered().await),
+        None => None,
+    };
+
+    Json(json!({
+        "data": {
+            "paused": config.chain.is_paused(),
+            "block_time": humantime::format_duration(config.chain.block_time()).to_string(),
+            "forward_buffer": forward_buffer,
+        },
+        "status": "OK",
+    }))
+}
+
+// POST /admin/seal
+async fn seal(State(config): State<Arc<Configuration>>) -> (StatusCode, Json<Value>) {
+    let config = Arc::clone(&config);
+    // no node runner to wait for
+    if let Mode::QueryOnly = config.mode {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "query-only node doesn't seal blocks",
+        );
+    }
+
+    // wait for the node runner to seal the pool
+    match config.chain.seal_now().await {
+        Ok(heights) => (
+            StatusCode::OK,
+            Json(json!({
+                "data": { "heights": heights },
+                "status": "OK",
+            })),
+        ),
+        Err(err) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("failed to seal block: {err}"),
+        ),
+    }
+}
+
+// POST /admin/pause
+async fn pause(State(config): State<Arc<Configuration>>) -> Json<Value> {
+    let config = Arc::clone(&config);
+    config.chain.set_paused(true);
+
+    Json(json!({
+        "message": "paused block factory",
+        "status": "OK",
+    }))
+}
+
+// POST /admin/resume
+async fn resume(State(config): State<Arc<Configuration>>) -> Json<Value> {
+    let config = Arc::clone(&config);
+    config.chain.set_paused(false);
+
+    // the pool may have filled up while paused
+    config
+        .chain
+        .request_seal_if_full(&config.block_trigger, config.seal_threshold)
+        .await;
+
+    Json(json!({
+        "message": "resumed block factory",
+        "status": "OK",
+    }))
+}
+
+// PUT /admin/block-time
+async fn block_time(
+    State(config): State<Arc<Configuration>>,
+    Json(payload): Json<BlockTimePayload>,
+) -> (StatusCode, Json<Value>) {
+    let config = Arc::clone(&config);
+
+    let block_time = match parse_block_time(&payload.block_time) {
+        Ok(block_time) => block_time,
+        Err(err) => return error(StatusCode::BAD_REQUEST, &err),
+    };
+
+    config.chain.set_block_time(block_time);
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "message": "changed block time",
+            "status": "OK",
+        })),
+    )
+}
+
+// POST /admin/backup
+async fn backup(State(config): State<Arc<Configuration>>) -> (StatusCode, Json<Value>) {
+    let config = Arc::clone(&config);
+
+    // the node keeps sealing, the backup holds the chain as of its start
+    match chain_backup::create(&config.persistence, &config.backup_dir).await {
+        Ok(manifest) => (
+            StatusCode::OK,
+            Json(json!({
+                "data": manifest,
+                "status": "OK",
+            })),
+        ),
+        Err(err) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("failed to back up database: {err}"),
+        ),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> (StatusCode, Json<Value>) {
+    (
+        status,
+        Json(json!({
+            "message": message,
+            "status": "ERROR",
+        })),
+    )
+}
@@ -1,16 +1,91 @@
-use crate::blockchain::Transaction;
+use crate::blockchain::{PendingTransaction, SealedBlock, Transaction, TransactionStatus};
 
 use anyhow::Result;
 use axum::{
-    extract::State,
-    response::Json,
-    routing::{get, put},
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Json,
+    },
+    routing::{get, post, put},
     Router,
 };
+use futures::Stream;
+use serde::Deserialize;
 use serde_json::{json, Value};
+use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time;
 
-use crate::config::Configuration;
+use super::paging::Paging;
+use super::search::{self, SearchParams};
+use crate::config::{Configuration, Mode};
+use crate::forwarder::{ForwardRequest, Forwarded};
+use crate::keys::check_signature;
+use crate::persistence::models::{BlockData, TransactionData};
+use crate::persistence::{models::Direction, NodePersistency};
+
+// amount of stored blocks read at a time when catching up a stream
+const STREAM_PAGE_SIZE: u32 = 100;
+
+// most blocks returned by one `GET /api/blocks`
+const MAX_BLOCKS_LIMIT: u32 = 100;
+
+// longest a status request may wait for its transaction to be sealed
+const MAX_STATUS_WAIT_SECS: u64 = 60;
+
+/// Why a submitted [`Transaction`] was refused
+struct Rejection {
+    // missing if the transaction couldn't be identified
+    hash: Option<String>,
+    reason: String,
+}
+
+#[derive(Deserialize)]
+struct TransactionStatusParams {
+    // seconds to wait for a pending transaction to be sealed (long-poll)
+    wait: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct AddressParams {
+    #[serde(default)]
+    direction: Direction,
+}
+
+#[derive(Deserialize)]
+struct BlocksParams {
+    // first height to return (inclusive)
+    #[serde(default = "genesis_height")]
+    from_height: u32,
+    limit: Option<u32>,
+}
+
+fn genesis_height() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+struct VerifyBlockRequest {
+    #[serde(flatten)]
+    block: BlockData,
+    // in block order, the hash of a current header covers theirs
+    #[serde(default)]
+    transactions: Vec<TransactionData>,
+}
+
+#[derive(Deserialize)]
+struct BlockStreamParams {
+    // replay stored blocks from this height (inclusive) before following new ones
+    from_height: Option<u32>,
+    // include the sealed transactions in each event
+    #[serde(default)]
+    transactions: bool,
+}
 
 // [kristian] TODO: standardize response objects
 pub fn router(shared_config: Arc<Configuration>) -> Result<Router> {
@@ -24,8 +99,24 @@ pub fn router(shared_config: Arc<Configuration>) -> Result<Router> {
         .route("/hello", get(hello_world))
         // PUT /api/transaction
         .route("/transaction", put(transaction))
+        // PUT /api/transaction/batch
+        .route("/transaction/batch", put(transaction_batch))
         // GET /api/transaction/pool
-        .route("/transaction/pool", get(tx_pool));
+        .route("/transaction/pool", get(tx_pool))
+        // GET /api/transaction/status/:hash
+        .route("/transaction/status/:hash", get(transaction_status))
+        // GET /api/address/:address
+        .route("/address/:address", get(address))
+        // GET /api/search?q=
+        .route("/search", get(search))
+        // GET /api/blocks?from_height=&limit=
+        .route("/blocks", get(blocks))
+        // POST /api/blocks/verify
+        .route("/blocks/verify", post(verify_block))
+        // GET /api/identity
+        .route("/identity", get(identity))
+        // GET /api/blocks/stream (server-sent events)
+        .route("/blocks/stream", get(block_stream));
 
     let api_group = Router::new()
         .nest("/api", api_routes)
@@ -46,18 +137,234 @@ async fn hello_world() -> Json<Value> {
 async fn transaction(
     State(config): State<Arc<Configuration>>,
     Json(payload): Json<Transaction>,
-) -> Json<Value> {
+) -> (StatusCode, Json<Value>) {
     let config = Arc::clone(&config);
-    let tx = payload;
+    if let Mode::QueryOnly = config.mode {
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(err) => return forward_error(err.into()),
+        };
+        return forward(
+            &config,
+            ForwardRequest {
+                path: "/api/transaction",
+                content_type: String::from("application/json"),
+                body: body.into(),
+            },
+        )
+        .await;
+    }
+
+    let tx = match admit(&config, payload).await {
+        Ok(tx) => tx,
+        Err(rejection) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "data": { "hash": rejection.hash },
+                    "message": rejection.reason,
+                    "status": "ERROR",
+                })),
+            )
+        }
+    };
+    let hash = tx.hash.clone();
+
     let pool_arc = Arc::clone(&config.chain.tx_pool);
 
-    let mut pool = pool_arc.lock().await;
-    pool.push(tx);
+    {
+        let mut pool = pool_arc.lock().await;
+        pool.push(tx);
+    } // lock drops here
 
-    Json(json!({
-        "message": "successfully added transaction to pool",
-        "status": "OK",
-    }))
+    // seal right away if the pool is big enough
+    config
+        .chain
+        .request_seal_if_full(&config.block_trigger, config.seal_threshold)
+        .await;
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "data": { "hash": hash },
+            "message": "successfully added transaction to pool",
+            "status": "OK",
+        })),
+    )
+}
+
+// PUT /transaction/batch
+async fn transaction_batch(
+    State(config): State<Arc<Configuration>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Json<Value>) {
+    let config = Arc::clone(&config);
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/json");
+
+    if let Mode::QueryOnly = config.mode {
+        return forward(
+            &config,
+            ForwardRequest {
+                path: "/api/transaction/batch",
+                content_type: content_type.to_owned(),
+                body,
+            },
+        )
+        .await;
+    }
+
+    // a JSON array, or one JSON transaction per line with `application/x-ndjson`
+    let is_ndjson = content_type.starts_with("application/x-ndjson");
+
+    let items: Vec<Result<Transaction, String>> = if is_ndjson {
+        body.split(|byte| *byte == b'\n')
+            .filter(|line| !line.trim_ascii().is_empty())
+            .map(|line| serde_json::from_slice(line).map_err(|err| err.to_string()))
+            .collect()
+    } else {
+        match serde_json::from_slice::<Vec<Value>>(&body) {
+            Ok(values) => values
+                .into_iter()
+                .map(|value| serde_json::from_value(value).map_err(|err| err.to_string()))
+                .collect(),
+            Err(err) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({
+                        "message": format!("invalid transaction batch: {err}"),
+                        "status": "ERROR",
+                    })),
+                )
+            }
+        }
+    };
+
+    let mut results = vec![];
+    let mut accepted = vec![];
+    for (index, item) in items.into_iter().enumerate() {
+        let admitted = match item {
+            Ok(tx) => admit(&config, tx).await,
+            Err(err) => Err(Rejection {
+                hash: None,
+                reason: format!("invalid transaction: {err}"),
+            }),
+        };
+
+        match admitted {
+            Ok(tx) => {
+                results.push(json!({
+                    "index": index,
+                    "state": "accepted",
+                    "hash": tx.hash,
+                }));
+                accepted.push(tx);
+            }
+            Err(rejection) => results.push(json!({
+                "index": index,
+                "state": "rejected",
+                "hash": rejection.hash,
+                "reason": rejection.reason,
+            })),
+        }
+    }
+
+    let accepted_count = accepted.len();
+    let pool_arc = Arc::clone(&config.chain.tx_pool);
+
+    {
+        // add the whole batch under one lock
+        let mut pool = pool_arc.lock().await;
+        for tx in accepted {
+            pool.push(tx);
+        }
+    } // lock drops here
+
+    // seal right away if the pool is big enough
+    config
+        .chain
+        .request_seal_if_full(&config.block_trigger, config.seal_threshold)
+        .await;
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "data": {
+                "accepted": accepted_count,
+                "rejected": results.len() - accepted_count,
+                "results": results,
+            },
+            "status": "OK",
+        })),
+    )
+}
+
+// query-only nodes never seal, they hand transactions to their primary
+// and pass its answer back, if they have one
+async fn forward(config: &Configuration, request: ForwardRequest) -> (StatusCode, Json<Value>) {
+    let Some(forwarder) = &config.forwarder else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "message": "query-only node without a primary doesn't accept transactions",
+                "status": "ERROR",
+            })),
+        );
+    };
+
+    match forwarder.forward(request).await {
+        Ok(Forwarded::Answered { status, body }) => (status, Json(body)),
+        Ok(Forwarded::Buffered { position }) => (
+            StatusCode::ACCEPTED,
+            Json(json!({
+                "data": { "buffered": position },
+                "message": format!(
+                    "primary {} is unreachable, request buffered for forwarding",
+                    forwarder.primary_url()
+                ),
+                "status": "OK",
+            })),
+        ),
+        Err(err) => forward_error(err),
+    }
+}
+
+fn forward_error(err: anyhow::Error) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::BAD_GATEWAY,
+        Json(json!({
+            "message": format!("failed to forward to primary: {err}"),
+            "status": "ERROR",
+        })),
+    )
+}
+
+/// Identifies a submitted [`Transaction`] and checks it can be sealed,
+/// refused transactions with a hash are remembered for status lookups
+async fn admit(config: &Configuration, tx: Transaction) -> Result<PendingTransaction, Rejection> {
+    // identify the transaction by its hash
+    let Some(hash_algorithm) = config.chain.hash_algorithm() else {
+        return Err(Rejection {
+            hash: None,
+            reason: String::from("the chain has no blocks yet to learn its hash algorithm from"),
+        });
+    };
+    let tx = PendingTransaction::new(tx, hash_algorithm);
+
+    // reject transactions that could never be sealed into a block
+    if config.block_limits.exceeds_max_bytes(tx.transaction.size()) {
+        let reason = String::from("transaction exceeds maximum block bytes");
+        config.chain.reject(tx.hash.clone(), reason.clone()).await;
+        return Err(Rejection {
+            hash: Some(tx.hash),
+            reason,
+        });
+    }
+
+    Ok(tx)
 }
 
 // GET /transaction/pool
@@ -68,7 +375,420 @@ async fn tx_pool(State(config): State<Arc<Configuration>>) -> Json<Value> {
     let pool = pool_arc.lock().await;
 
     Json(json!({
-        "data": *pool,
+        "data": pool.ordered(),
+        "status": "OK",
+    }))
+}
+
+// GET /transaction/status/:hash
+async fn transaction_status(
+    State(config): State<Arc<Configuration>>,
+    Path(hash): Path<String>,
+    Query(params): Query<TransactionStatusParams>,
+) -> (StatusCode, Json<Value>) {
+    let config = Arc::clone(&config);
+    let wait = Duration::from_secs(params.wait.unwrap_or(0).min(MAX_STATUS_WAIT_SECS));
+    let deadline = time::Instant::now() + wait;
+
+    // subscribe before the first lookup so the seal can't be missed
+    let mut sealed_blocks = config.chain.subscribe_sealed_blocks();
+
+    loop {
+        let status = match config
+            .chain
+            .transaction_status(&config.persistence, &hash)
+            .await
+        {
+            Ok(Some(status)) => status,
+            Ok(None) => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(json!({
+                        "message": "transaction not found",
+                        "status": "ERROR",
+                    })),
+                )
+            }
+            Err(err) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({
+                        "message": format!("failed to read transaction status: {err}"),
+                        "status": "ERROR",
+                    })),
+                )
+            }
+        };
+
+        // only a pending or sealing transaction can still change
+        if matches!(
+            status,
+            TransactionStatus::Pending { .. } | TransactionStatus::Sealing
+        ) && wait_for_seal(&mut sealed_blocks, &hash, deadline).await
+        {
+            continue;
+        }
+
+        let mut data = json!(status);
+        data["hash"] = json!(hash);
+
+        return (
+            StatusCode::OK,
+            Json(json!({
+                "data": data,
+                "status": "OK",
+            })),
+        );
+    }
+}
+
+/// Waits for a block that may hold the transaction with `hash`,
+/// returns false once the `deadline` passed
+async fn wait_for_seal(
+    sealed_blocks: &mut broadcast::Receiver<Arc<SealedBlock>>,
+    hash: &str,
+    deadline: time::Instant,
+) -> bool {
+    loop {
+        match time::timeout_at(deadline, sealed_blocks.recv()).await {
+            Ok(Ok(sealed_block)) => {
+                if sealed_block.transactions.iter().any(|tx| tx.hash == hash) {
+                    return true;
+                }
+            }
+            // missed some blocks, look it up again
+            Ok(Err(RecvError::Lagged(_))) => return true,
+            Ok(Err(RecvError::Closed)) | Err(_) => return false,
+        }
+    }
+}
+
+// GET /address/:address
+async fn address(
+    State(config): State<Arc<Configuration>>,
+    Path(address): Path<String>,
+    Query(params): Query<AddressParams>,
+    Query(paging): Query<Paging>,
+) -> (StatusCode, Json<Value>) {
+    let config = Arc::clone(&config);
+    let max_height = config.chain.snapshot().height();
+
+    let counts = config
+        .persistence
+        .count_address_transaction_data(&address, max_height)
+        .await;
+    let transactions = config
+        .persistence
+        .read_address_transaction_data(
+            &address,
+            params.direction,
+            max_height,
+            paging.limit(),
+            paging.offset(),
+        )
+        .await;
+
+    match (counts, transactions) {
+        (Ok((sent, received)), Ok(transactions)) => (
+            StatusCode::OK,
+            Json(json!({
+                "data": {
+                    "address": address,
+                    "sent": sent,
+                    "received": received,
+                    "page": paging.page(),
+                    "limit": paging.limit(),
+                    "transactions": transactions,
+                },
+                "status": "OK",
+            })),
+        ),
+        (Err(err), _) | (_, Err(err)) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "message": format!("failed to read address activity: {err}"),
+                "status": "ERROR",
+            })),
+        ),
+    }
+}
+
+// GET /search
+async fn search(
+    State(config): State<Arc<Configuration>>,
+    Query(params): Query<SearchParams>,
+) -> (StatusCode, Json<Value>) {
+    let config = Arc::clone(&config);
+
+    match search::resolve(&config, &params.q).await {
+        Ok(Some(result)) => {
+            let mut data = json!(result);
+            data["path"] = json!(result.path());
+
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "data": data,
+                    "status": "OK",
+                })),
+            )
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "message": "no block, transaction or address matches the query",
+                "status": "ERROR",
+            })),
+        ),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "message": format!("failed to search: {err}"),
+                "status": "ERROR",
+            })),
+        ),
+    }
+}
+
+// GET /blocks
+async fn blocks(
+    State(config): State<Arc<Configuration>>,
+    Query(params): Query<BlocksParams>,
+) -> (StatusCode, Json<Value>) {
+    let config = Arc::clone(&config);
+    let snapshot = config.chain.snapshot();
+    let limit = params
+        .limit
+        .unwrap_or(MAX_BLOCKS_LIMIT)
+        .clamp(1, MAX_BLOCKS_LIMIT);
+
+    let blocks = match config
+        .persistence
+        .read_block_data_from_height(params.from_height, limit)
+        .await
+    {
+        Ok(blocks) => blocks,
+        Err(err) => return blocks_error(err),
+    };
+
+    // stored after the snapshot
+    let mut sealed_blocks = vec![];
+    for block in blocks
+        .into_iter()
+        .take_while(|block| block.height <= snapshot.height())
+    {
+        let transactions = match config
+            .persistence
+            .read_block_transaction_data(block.height)
+            .await
+        {
+            Ok(transactions) => transactions,
+            Err(err) => return blocks_error(err),
+        };
+        sealed_blocks.push(SealedBlock {
+            block,
+            transactions,
+        });
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "data": {
+                "tip": snapshot.height(),
+                "hash_algorithm": config.chain.hash_algorithm(),
+                "blocks": sealed_blocks,
+            },
+            "status": "OK",
+        })),
+    )
+}
+
+// POST /blocks/verify
+async fn verify_block(
+    State(config): State<Arc<Configuration>>,
+    Json(request): Json<VerifyBlockRequest>,
+) -> Json<Value> {
+    let config = Arc::clone(&config);
+    let VerifyBlockRequest {
+        block,
+        transactions,
+    } = request;
+
+    // the hash covers the header and the transaction hashes,
+    // the signature covers the hash
+    let checked = match config.chain.hash_algorithm() {
+        None => Err(String::from("the local chain has no hash algorithm yet")),
+        Some(hash_algorithm) => match block.computed_hash(hash_algorithm, &transactions) {
+            None => Err(format!("unsupported header version {}", block.version)),
+            Some(hash) if hash != block.hash => {
+                Err(format!("hash {} doesn't match its header", block.hash))
+            }
+            Some(_) => match transactions.iter().find(|tx| {
+                tx.computed_hash(hash_algorithm, block.version).as_deref() != Some(&tx.hash)
+            }) {
+                Some(tx) => Err(format!(
+                    "transaction hash {} doesn't match its fields",
+                    tx.hash
+                )),
+                None => check_signature(&block),
+            },
+        },
+    };
+
+    // signed by this node, or by the producer whose turn it was
+    let trusted = checked.is_ok()
+        && match (&config.producers, &block.producer) {
+            (Some(producers), _) => producers.check(&block).is_ok(),
+            (None, Some(producer)) => config.public_key.as_ref() == Some(producer),
+            (None, None) => false,
+        };
+
+    Json(json!({
+        "data": {
+            "height": block.height,
+            "hash": block.hash,
+            "producer": block.producer,
+            "valid": checked.is_ok(),
+            "trusted": trusted,
+            "reason": checked.err(),
+        },
+        "status": "OK",
+    }))
+}
+
+// GET /identity
+async fn identity(State(config): State<Arc<Configuration>>) -> Json<Value> {
+    let config = Arc::clone(&config);
+
+    Json(json!({
+        "data": {
+            "public_key": config.public_key,
+            "hash_algorithm": config.chain.hash_algorithm(),
+            "producers": config.producers.as_ref().map(|producers| producers.keys()),
+        },
         "status": "OK",
     }))
 }
+
+// GET /blocks/stream
+async fn block_stream(
+    State(config): State<Arc<Configuration>>,
+    headers: HeaderMap,
+    Query(params): Query<BlockStreamParams>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let config = Arc::clone(&config);
+    let include_transactions = params.transactions;
+
+    // reconnecting EventSource clients send the last height they received
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u32>().ok());
+    // an id past the last possible height is ignored
+    let from_height = match last_event_id.and_then(|height| height.checked_add(1)) {
+        Some(height) => Some(height),
+        None => params.from_height,
+    };
+
+    let stream = async_stream::stream! {
+        // subscribe before catching up so no block falls in between
+        let mut sealed_blocks = config.chain.subscribe_sealed_blocks();
+        // next height to send, live blocks start past the tip when not resuming,
+        // so blocks missed by lagging behind are still known to catch up on
+        let mut next_height = match from_height {
+            Some(height) => height,
+            None => config.chain.snapshot().height() + 1,
+        };
+
+        loop {
+            // catch up on stored blocks
+            loop {
+                let blocks = match config
+                    .persistence
+                    .read_block_data_from_height(next_height, STREAM_PAGE_SIZE)
+                    .await
+                {
+                    Ok(blocks) => blocks,
+                    Err(err) => {
+                        yield Ok(error_event(err));
+                        return;
+                    }
+                };
+
+                if blocks.is_empty() {
+                    break;
+                }
+
+                for block in blocks {
+                    let transactions = if include_transactions {
+                        match config.persistence.read_block_transaction_data(block.height).await {
+                            Ok(transactions) => transactions,
+                            Err(err) => {
+                                yield Ok(error_event(err));
+                                return;
+                            }
+                        }
+                    } else {
+                        vec![]
+                    };
+
+                    next_height = block.height + 1;
+                    yield Ok(block_event(&SealedBlock { block, transactions }, include_transactions));
+                }
+            }
+
+            // follow newly sealed blocks
+            loop {
+                match sealed_blocks.recv().await {
+                    Ok(sealed_block) => {
+                        // already sent while catching up
+                        if sealed_block.block.height < next_height {
+                            continue;
+                        }
+                        next_height = sealed_block.block.height + 1;
+                        yield Ok(block_event(&sealed_block, include_transactions));
+                    }
+                    // missed some blocks, catch up on them from storage
+                    Err(RecvError::Lagged(_)) => break,
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn block_event(sealed_block: &SealedBlock, include_transactions: bool) -> Event {
+    let data = if include_transactions {
+        json!(sealed_block)
+    } else {
+        json!({ "block": sealed_block.block })
+    };
+
+    Event::default()
+        .event("block")
+        .id(sealed_block.block.height.to_string())
+        .data(data.to_string())
+}
+
+fn blocks_error(err: anyhow::Error) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({
+            "message": format!("failed to read blocks: {err}"),
+            "status": "ERROR",
+        })),
+    )
+}
+
+fn error_event(err: anyhow::Error) -> Event {
+    Event::default().event("error").data(
+        json!({
+            "message": format!("failed to read blocks: {err}"),
+            "status": "ERROR",
+        })
+        .to_string(),
+    )
+}
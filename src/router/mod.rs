@@ -1,2 +1,5 @@
+pub mod admin;
 pub mod api;
+pub mod paging;
+pub mod search;
 pub mod ui;
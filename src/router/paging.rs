@@ -0,0 +1,29 @@
+use serde::Deserialize;
+
+// page size when none is given
+const DEFAULT_PAGE_SIZE: u32 = 20;
+// largest page size a client may ask for
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// `?page=&limit=` query parameters, pages start at 1
+#[derive(Clone, Debug, Deserialize)]
+pub struct Paging {
+    page: Option<u32>,
+    limit: Option<u32>,
+}
+
+impl Paging {
+    pub fn page(&self) -> u32 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    pub fn offset(&self) -> u32 {
+        (self.page() - 1).saturating_mul(self.limit())
+    }
+}
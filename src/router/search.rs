@@ -0,0 +1,94 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Configuration;
+use crate::persistence::NodePersistency;
+
+/// `?q=` query parameter of a search
+#[derive(Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    pub q: String,
+}
+
+/// What a search query resolved to
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SearchResult {
+    Block { height: u32, hash: String },
+    Transaction { hash: String },
+    Address { address: String },
+}
+
+impl SearchResult {
+    /// Path of the explorer page showing the result
+    pub fn path(&self) -> String {
+        match self {
+            SearchResult::Block { height, .. } => format!("/block/{height}"),
+            SearchResult::Transaction { hash } => format!("/transaction/{hash}"),
+            SearchResult::Address { address } => address_path(address),
+        }
+    }
+}
+
+/// Path of the explorer page of `address`, which may hold any character
+pub fn address_path(address: &str) -> String {
+    format!("/address/{}", urlencoding::encode(address))
+}
+
+/// Works out if `query` is a block height, block hash,
+/// transaction hash or address (in that order)
+pub async fn resolve(config: &Configuration, query: &str) -> Result<Option<SearchResult>> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(None);
+    }
+    // nothing stored after the snapshot is found
+    let max_height = config.chain.snapshot().height();
+
+    if let Ok(height) = query.parse::<u32>() {
+        if height <= max_height {
+            if let Some(block) = config.persistence.read_block_data_by_height(height).await? {
+                return Ok(Some(SearchResult::Block {
+                    height: block.height,
+                    hash: block.hash,
+                }));
+            }
+        }
+    }
+
+    if let Some(block) = config.persistence.read_block_data_by_hash(query).await? {
+        if block.height <= max_height {
+            return Ok(Some(SearchResult::Block {
+                height: block.height,
+                hash: block.hash,
+            }));
+        }
+    }
+
+    // pending or sealed transactions, rejected ones have no page
+    if config.chain.tx_pool.lock().await.get(query).is_some()
+        || config
+            .persistence
+            .read_transaction_data_by_hash(query)
+            .await?
+            .is_some_and(|tx| tx.block_height <= max_height)
+    {
+        return Ok(Some(SearchResult::Transaction {
+            hash: query.to_owned(),
+        }));
+    }
+
+    // only addresses that sent or received something are known
+    let (sent, received) = config
+        .persistence
+        .count_address_transaction_data(query, max_height)
+        .await?;
+    if sent + received > 0 {
+        return Ok(Some(SearchResult::Address {
+            address: query.to_owned(),
+        }));
+    }
+
+    Ok(None)
+}
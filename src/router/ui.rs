@@ -3,26 +3,106 @@ use anyhow::Result;
 use askama::Template;
 
 use axum::{
+    extract::{Path, Query, State},
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{Html, IntoResponse, Redirect, Response},
     routing::get,
     Router,
 };
+use std::sync::Arc;
+
+use super::paging::Paging;
+use super::search::{self, SearchParams};
+use crate::blockchain::{PendingTransaction, TransactionStatus};
+use crate::config::Configuration;
+use crate::persistence::{
+    models::{BlockData, Direction, TransactionData},
+    NodePersistency,
+};
+
+// custom template filters, askama looks them up in `filters`
+mod filters {
+    /// Explorer path of an address, encoded the same way as search redirects
+    pub fn address_path(address: &str) -> askama::Result<String> {
+        Ok(super::search::address_path(address))
+    }
+}
 
 #[derive(Template)]
 #[template(path = "index.html")]
-struct IndexTemplate {}
+struct IndexTemplate {
+    blocks: Vec<BlockData>,
+    page: u32,
+    has_next: bool,
+}
+
+#[derive(Template)]
+#[template(path = "block.html")]
+struct BlockTemplate {
+    block: BlockData,
+    transactions: Vec<TransactionData>,
+}
+
+#[derive(Template)]
+#[template(path = "transaction.html")]
+struct TransactionTemplate {
+    hash: String,
+    from: String,
+    to: String,
+    fee: u32,
+    instruction: String,
+    status: TransactionStatus,
+}
+
+#[derive(Template)]
+#[template(path = "address.html")]
+struct AddressTemplate {
+    address: String,
+    sent: u32,
+    received: u32,
+    transactions: Vec<TransactionData>,
+    page: u32,
+    has_next: bool,
+}
+
+#[derive(Template)]
+#[template(path = "mempool.html")]
+struct MempoolTemplate {
+    // pool size, the page holds part of it
+    total: usize,
+    // position of the first transaction on the page
+    offset: usize,
+    transactions: Vec<PendingTransaction>,
+    page: u32,
+    has_next: bool,
+}
+
+#[derive(Template)]
+#[template(path = "search_not_found.html")]
+struct SearchNotFoundTemplate {
+    query: String,
+}
 
 #[derive(Template)]
 #[template(path = "404.html")]
 struct NotFoundTemplate {}
 
-pub fn router() -> Result<Router> {
+pub fn router(shared_config: Arc<Configuration>) -> Result<Router> {
     let ui_routes = Router::new()
         // / -> block-explorer
-        .route("/", get(block_explorer));
+        .route("/", get(block_explorer))
+        // /block/:height -> block detail
+        .route("/block/:height", get(block))
+        // /transaction/:hash -> transaction detail
+        .route("/transaction/:hash", get(transaction))
+        // /address/:address -> address activity
+        .route("/address/:address", get(address))
+        // /mempool -> pending transactions
+        .route("/mempool", get(mempool))
+        // /search?q= -> redirect to the matching page
+        .route("/search", get(search));
 
-    let ui_group = Router::new().nest("/", ui_routes);
+    let ui_group = Router::new().nest("/", ui_routes).with_state(shared_config);
 
     Ok(ui_group)
 }
@@ -32,13 +112,241 @@ pub fn router() -> Result<Router> {
 // global 404 (not found)
 pub async fn not_found() -> impl IntoResponse {
     let template = NotFoundTemplate {};
-    HtmlTemplate(template)
+    (StatusCode::NOT_FOUND, HtmlTemplate(template))
 }
 
 // get block explorer
-async fn block_explorer() -> impl IntoResponse {
-    let template = IndexTemplate {};
-    HtmlTemplate(template)
+async fn block_explorer(
+    State(config): State<Arc<Configuration>>,
+    Query(paging): Query<Paging>,
+) -> Response {
+    let config = Arc::clone(&config);
+    let snapshot = config.chain.snapshot();
+
+    // read one extra block to know if there's a next page,
+    // older pages aren't in memory
+    let mut blocks = match snapshot.recent_blocks(paging.limit() + 1, paging.offset()) {
+        Some(blocks) => blocks,
+        None => match config
+            .persistence
+            .read_recent_block_data(snapshot.height(), paging.limit() + 1, paging.offset())
+            .await
+        {
+            Ok(blocks) => blocks,
+            Err(err) => return internal_error(err),
+        },
+    };
+    let has_next = blocks.len() > paging.limit() as usize;
+    blocks.truncate(paging.limit() as usize);
+
+    let template = IndexTemplate {
+        blocks,
+        page: paging.page(),
+        has_next,
+    };
+    HtmlTemplate(template).into_response()
+}
+
+// get block detail
+async fn block(State(config): State<Arc<Configuration>>, Path(height): Path<u32>) -> Response {
+    let config = Arc::clone(&config);
+    let snapshot = config.chain.snapshot();
+
+    if height > snapshot.height() {
+        return not_found().await.into_response();
+    }
+    if let Some(sealed) = snapshot.block(height) {
+        let template = BlockTemplate {
+            block: sealed.block.clone(),
+            transactions: sealed.transactions.clone(),
+        };
+        return HtmlTemplate(template).into_response();
+    }
+
+    let block = match config.persistence.read_block_data_by_height(height).await {
+        Ok(Some(block)) => block,
+        Ok(None) => return not_found().await.into_response(),
+        Err(err) => return internal_error(err),
+    };
+    let transactions = match config
+        .persistence
+        .read_block_transaction_data(block.height)
+        .await
+    {
+        Ok(transactions) => transactions,
+        Err(err) => return internal_error(err),
+    };
+
+    let template = BlockTemplate {
+        block,
+        transactions,
+    };
+    HtmlTemplate(template).into_response()
+}
+
+// get transaction detail
+async fn transaction(
+    State(config): State<Arc<Configuration>>,
+    Path(hash): Path<String>,
+) -> Response {
+    let config = Arc::clone(&config);
+
+    let status = match config
+        .chain
+        .transaction_status(&config.persistence, &hash)
+        .await
+    {
+        Ok(Some(status)) => status,
+        Ok(None) => return not_found().await.into_response(),
+        Err(err) => return internal_error(err),
+    };
+
+    // rejected transactions never made it into the pool
+    if let TransactionStatus::Rejected { .. } = status {
+        return not_found().await.into_response();
+    }
+
+    // pending and sealing transactions are in memory until their block is published
+    let unpublished = match status {
+        TransactionStatus::Sealed { .. } => None,
+        _ => config.chain.unpublished_transaction(&hash).await,
+    };
+    let template = match unpublished {
+        Some(tx) => TransactionTemplate {
+            instruction: String::from_utf8_lossy(&tx.transaction.instruction).into_owned(),
+            hash: tx.hash,
+            from: tx.transaction.from,
+            to: tx.transaction.to,
+            fee: tx.transaction.fee,
+            status,
+        },
+        None => match config
+            .persistence
+            .read_transaction_data_by_hash(&hash)
+            .await
+        {
+            Ok(Some(tx)) => TransactionTemplate {
+                instruction: tx.instruction_text(),
+                hash: tx.hash,
+                from: tx.from,
+                to: tx.to,
+                fee: tx.fee,
+                status,
+            },
+            Ok(None) => return not_found().await.into_response(),
+            Err(err) => return internal_error(err),
+        },
+    };
+
+    HtmlTemplate(template).into_response()
+}
+
+// get address activity
+async fn address(
+    State(config): State<Arc<Configuration>>,
+    Path(address): Path<String>,
+    Query(paging): Query<Paging>,
+) -> Response {
+    let config = Arc::clone(&config);
+    let max_height = config.chain.snapshot().height();
+
+    let (sent, received) = match config
+        .persistence
+        .count_address_transaction_data(&address, max_height)
+        .await
+    {
+        Ok(counts) => counts,
+        Err(err) => return internal_error(err),
+    };
+
+    // read one extra transaction to know if there's a next page
+    let mut transactions = match config
+        .persistence
+        .read_address_transaction_data(
+            &address,
+            Direction::All,
+            max_height,
+            paging.limit() + 1,
+            paging.offset(),
+        )
+        .await
+    {
+        Ok(transactions) => transactions,
+        Err(err) => return internal_error(err),
+    };
+    let has_next = transactions.len() > paging.limit() as usize;
+    transactions.truncate(paging.limit() as usize);
+
+    let template = AddressTemplate {
+        address,
+        sent,
+        received,
+        transactions,
+        page: paging.page(),
+        has_next,
+    };
+    HtmlTemplate(template).into_response()
+}
+
+// get pending transactions
+async fn mempool(
+    State(config): State<Arc<Configuration>>,
+    Query(paging): Query<Paging>,
+) -> Response {
+    let config = Arc::clone(&config);
+    let offset = paging.offset() as usize;
+
+    let (total, transactions) = {
+        let pool = config.chain.tx_pool.lock().await;
+        let transactions = pool
+            .ordered()
+            .into_iter()
+            .skip(offset)
+            .take(paging.limit() as usize)
+            .map(|pooled| pooled.transaction.clone())
+            .collect();
+        (pool.len(), transactions)
+    }; // lock drops here
+
+    let template = MempoolTemplate {
+        total,
+        offset,
+        transactions,
+        page: paging.page(),
+        has_next: offset + (paging.limit() as usize) < total,
+    };
+    HtmlTemplate(template).into_response()
+}
+
+// search by block height or hash, transaction hash, or address
+async fn search(
+    State(config): State<Arc<Configuration>>,
+    Query(params): Query<SearchParams>,
+) -> Response {
+    let config = Arc::clone(&config);
+
+    if params.q.trim().is_empty() {
+        return Redirect::to("/").into_response();
+    }
+
+    match search::resolve(&config, &params.q).await {
+        Ok(Some(result)) => Redirect::to(&result.path()).into_response(),
+        Ok(None) => {
+            let template = SearchNotFoundTemplate {
+                query: params.q.trim().to_owned(),
+            };
+            (StatusCode::NOT_FOUND, HtmlTemplate(template)).into_response()
+        }
+        Err(err) => internal_error(err),
+    }
+}
+
+fn internal_error(err: anyhow::Error) -> Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        format!("Failed to read chain data. Error: {}", err),
+    )
+        .into_response()
 }
 
 //---- ASKAMA TEMPLATING -----
@@ -60,3 +368,217 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::Transaction;
+    use crate::hasher::HashAlgorithm;
+    use crate::service::Assets;
+
+    // absolute (http://, https://) and protocol-relative (//host) URLs,
+    // `//` followed by a space is a comment, not a URL
+    fn off_host_urls(text: &str) -> Vec<String> {
+        let is_delimiter = |c: char| c.is_whitespace() || "\"'()<>".contains(c);
+
+        let mut urls = vec![];
+        for (index, _) in text.match_indices("//") {
+            let rest = &text[index + 2..];
+            if !rest.starts_with(|c: char| !is_delimiter(c) && c != '/') {
+                continue;
+            }
+            let end = rest.find(is_delimiter).unwrap_or(rest.len());
+            urls.push(format!("//{}", &rest[..end]));
+        }
+        urls
+    }
+
+    // credits in comments aren't fetched
+    fn strip_css_comments(css: &str) -> String {
+        let mut stripped = String::new();
+        let mut rest = css;
+        while let Some(start) = rest.find("/*") {
+            stripped.push_str(&rest[..start]);
+            rest = rest[start..]
+                .find("*/")
+                .map_or("", |end| &rest[start + end + 2..]);
+        }
+        stripped.push_str(rest);
+        stripped
+    }
+
+    fn block() -> BlockData {
+        BlockData {
+            version: 2,
+            id: String::from("0192a0c4-8e00-7000-8000-000000000000"),
+            hash: String::from("ab"),
+            nonce: 12,
+            height: 3,
+            prev_block_hash: String::from("aa"),
+            producer: Some(String::from("cd")),
+            signature: Some(String::from("ef")),
+        }
+    }
+
+    fn transaction() -> TransactionData {
+        TransactionData {
+            id: String::from("0192a0c4-8e00-7000-8000-000000000001"),
+            hash: String::from("cd"),
+            from: String::from("alice"),
+            to: String::from("bob"),
+            instruction: b"hi".to_vec(),
+            fee: 1,
+            block_height: 3,
+            position: 0,
+        }
+    }
+
+    #[test]
+    fn pages_reference_no_off_host_urls() {
+        let pending = PendingTransaction::new(
+            Transaction {
+                from: String::from("alice"),
+                to: String::from("bob"),
+                instruction: b"hi".to_vec(),
+                fee: 1,
+            },
+            HashAlgorithm::Sha256,
+        );
+
+        let pages = [
+            (
+                "index",
+                IndexTemplate {
+                    blocks: vec![block()],
+                    page: 2,
+                    has_next: true,
+                }
+                .render(),
+            ),
+            (
+                "block",
+                BlockTemplate {
+                    block: block(),
+                    transactions: vec![transaction()],
+                }
+                .render(),
+            ),
+            (
+                "transaction",
+                TransactionTemplate {
+                    hash: String::from("cd"),
+                    from: String::from("alice"),
+                    to: String::from("bob"),
+                    fee: 1,
+                    instruction: String::from("hi"),
+                    status: TransactionStatus::Sealed {
+                        block_height: 3,
+                        block_hash: String::from("ab"),
+                        position: 0,
+                    },
+                }
+                .render(),
+            ),
+            (
+                "address",
+                AddressTemplate {
+                    address: String::from("alice"),
+                    sent: 1,
+                    received: 0,
+                    transactions: vec![transaction()],
+                    page: 2,
+                    has_next: true,
+                }
+                .render(),
+            ),
+            (
+                "mempool",
+                MempoolTemplate {
+                    total: 21,
+                    offset: 20,
+                    transactions: vec![pending],
+                    page: 2,
+                    has_next: false,
+                }
+                .render(),
+            ),
+            (
+                "search_not_found",
+                SearchNotFoundTemplate {
+                    query: String::from("missing"),
+                }
+                .render(),
+            ),
+            ("404", NotFoundTemplate {}.render()),
+        ];
+
+        for (name, html) in pages {
+            let html = html.unwrap_or_else(|err| panic!("{name} failed to render: {err}"));
+            assert_eq!(off_host_urls(&html), Vec::<String>::new(), "{name} page");
+        }
+    }
+
+    #[test]
+    fn address_links_match_search_redirects() {
+        let address = "a/b c?d";
+        let path = search::SearchResult::Address {
+            address: address.to_owned(),
+        }
+        .path();
+        assert_eq!(path, "/address/a%2Fb%20c%3Fd");
+
+        let transaction = TransactionData {
+            from: address.to_owned(),
+            ..transaction()
+        };
+        let link = format!("href=\"{path}\"");
+        let pages = [
+            TransactionTemplate {
+                hash: transaction.hash.clone(),
+                from: transaction.from.clone(),
+                to: transaction.to.clone(),
+                fee: transaction.fee,
+                instruction: String::from("hi"),
+                status: TransactionStatus::Sealing,
+            }
+            .render()
+            .unwrap(),
+            AddressTemplate {
+                address: address.to_owned(),
+                sent: 1,
+                received: 0,
+                transactions: vec![transaction],
+                page: 1,
+                has_next: false,
+            }
+            .render()
+            .unwrap(),
+        ];
+        for page in pages {
+            assert!(page.contains(&link), "no {link} in {page}");
+        }
+    }
+
+    #[test]
+    fn stylesheets_reference_no_off_host_urls() {
+        let stylesheets: Vec<_> = Assets::iter()
+            .filter(|path| path.ends_with(".css"))
+            .collect();
+        assert!(!stylesheets.is_empty());
+
+        for path in stylesheets {
+            let css = Assets::get(&path).unwrap();
+            let css = strip_css_comments(std::str::from_utf8(&css.data).unwrap());
+            assert_eq!(off_host_urls(&css), Vec::<String>::new(), "{path}");
+        }
+    }
+
+    #[test]
+    fn finds_off_host_urls() {
+        let html = r#"<a href="https://example.com/x">x</a> <img src="//cdn.example.com/y.png"> // comment"#;
+        assert_eq!(
+            off_host_urls(html),
+            ["//example.com/x", "//cdn.example.com/y.png"]
+        );
+    }
+}
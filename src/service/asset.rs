@@ -1,17 +1,75 @@
-use anyhow::{Context, Result};
-use axum::Router;
-use std::env;
+use anyhow::Result;
+use axum::{
+    handler::HandlerWithoutStateExt,
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use rust_embed::RustEmbed;
+use std::path::Path;
 use tower_http::services::ServeDir;
 
-pub fn service() -> Result<Router> {
+// compiled into the binary, so the factory runs from any directory
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+pub(crate) struct Assets;
+
+// embedded assets only change with a new binary, the ETag catches that
+const CACHE_CONTROL: &str = "public, max-age=3600";
+
+pub fn service(override_dir: Option<&Path>) -> Result<Router> {
     let assets_route = Router::new();
 
-    let current_dir = env::current_dir()?;
-    let current_dir = current_dir
-        .to_str()
-        .context("Cannot parse current directory to string")?;
-    let assets_group =
-        assets_route.nest_service("/assets", ServeDir::new(format!("{current_dir}/assets")));
+    let assets_group = match override_dir {
+        // files in the override directory win, the rest are embedded
+        Some(dir) => assets_route.nest_service(
+            "/assets",
+            ServeDir::new(dir).fallback(embedded_asset.into_service()),
+        ),
+        None => assets_route.nest_service("/assets", embedded_asset.into_service()),
+    };
 
     Ok(assets_group)
 }
+
+// serve an embedded asset
+async fn embedded_asset(uri: Uri, headers: HeaderMap) -> Response {
+    let path = uri.path().trim_start_matches('/');
+    let Some(asset) = Assets::get(path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // strong ETag from the content hash
+    let hash: String = asset
+        .metadata
+        .sha256_hash()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let etag = format!("\"{hash}\"");
+
+    let cache_headers = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, String::from(CACHE_CONTROL)),
+    ];
+
+    // If-None-Match can list several ETags
+    let is_cached = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == "*" || tag.trim() == etag)
+        });
+    if is_cached {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+
+    (
+        [(header::CONTENT_TYPE, asset.metadata.mimetype().to_owned())],
+        cache_headers,
+        asset.data,
+    )
+        .into_response()
+}
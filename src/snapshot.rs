@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::blockchain::SealedBlock;
+use crate::persistence::models::BlockData;
+
+/// Amount of recent blocks every [`ChainSnapshot`] keeps in memory
+pub const RECENT_BLOCKS_CAPACITY: usize = 128;
+
+/// The chain up to a tip, with the most recent blocks in memory
+///
+/// Stored blocks never change once sealed, so reads bounded by the tip
+/// of one snapshot agree with each other even while new blocks arrive
+#[derive(Clone, Debug, Default)]
+pub struct ChainSnapshot {
+    // lowest height first, the tip is last
+    recent: VecDeque<Arc<SealedBlock>>,
+}
+
+impl ChainSnapshot {
+    /// Creates a snapshot from consecutive blocks, lowest height first
+    pub fn from_blocks(blocks: impl IntoIterator<Item = SealedBlock>) -> Self {
+        let mut snapshot = Self::default();
+        for sealed in blocks {
+            snapshot.push(Arc::new(sealed));
+        }
+        snapshot
+    }
+
+    pub fn tip(&self) -> Option<&BlockData> {
+        self.recent.back().map(|sealed| &sealed.block)
+    }
+
+    /// Height of the tip, `0` before genesis is stored
+    pub fn height(&self) -> u32 {
+        self.tip().map_or(0, |tip| tip.height)
+    }
+
+    /// The block at `height`, if it's in memory
+    pub fn block(&self, height: u32) -> Option<&Arc<SealedBlock>> {
+        let lowest = self.recent.front()?.block.height;
+        let index = height.checked_sub(lowest)?;
+        self.recent.get(index as usize)
+    }
+
+    /// Up to `limit` blocks skipping `offset`, highest first,
+    /// `None` if some of them aren't in memory
+    pub fn recent_blocks(&self, limit: u32, offset: u32) -> Option<Vec<BlockData>> {
+        let highest = i64::from(self.height()) - i64::from(offset);
+        if highest < 1 {
+            return Some(vec![]);
+        }
+        let lowest = (highest - i64::from(limit) + 1).max(1);
+
+        if lowest < i64::from(self.recent.front()?.block.height) {
+            return None;
+        }
+
+        Some(
+            (lowest..=highest)
+                .rev()
+                .filter_map(|height| self.block(height as u32))
+                .map(|sealed| sealed.block.clone())
+                .collect(),
+        )
+    }
+
+    /// Returns a snapshot with `sealed` as its tip
+    pub fn with_block(&self, sealed: Arc<SealedBlock>) -> Self {
+        let mut snapshot = self.clone();
+        snapshot.push(sealed);
+        snapshot
+    }
+
+    fn push(&mut self, sealed: Arc<SealedBlock>) {
+        if self.recent.len() >= RECENT_BLOCKS_CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(sealed);
+    }
+}
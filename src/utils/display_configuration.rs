@@ -1,7 +1,7 @@
-use crate::config::Mode;
+use crate::config::{BlockTrigger, Configuration, EmptyBlockPolicy, Mode};
 use comfy_table::{presets::UTF8_FULL, *};
 
-pub fn display_configuration(port: &u32, block_time: &u32, mode: &Mode) {
+pub fn display_configuration(config: &Configuration) {
     // display configuration from flags
     let mut table = Table::new();
     // resolve temporary borrow error
@@ -17,16 +17,125 @@ pub fn display_configuration(port: &u32, block_time: &u32, mode: &Mode) {
             Cell::new("Value").add_attribute(Attribute::Bold),
         ]);
 
-    table.add_row(vec![Cell::new("Port"), Cell::new(port)]);
-    table.add_row(vec![Cell::new("Block Time"), Cell::new(block_time)]);
+    table.add_row(vec![
+        Cell::new("Config File"),
+        Cell::new(match &config.config_file {
+            Some(path) => path.display().to_string(),
+            None => String::from("None"),
+        }),
+    ]);
+    table.add_row(vec![
+        Cell::new("Database URL"),
+        Cell::new(&config.database_url),
+    ]);
+    table.add_row(vec![
+        Cell::new("Bind Address"),
+        Cell::new(config.bind_address),
+    ]);
+    table.add_row(vec![Cell::new("Port"), Cell::new(config.port)]);
+    table.add_row(vec![
+        Cell::new("Block Time"),
+        Cell::new(humantime::format_duration(config.block_time)),
+    ]);
+    table.add_row(vec![
+        Cell::new("Trigger"),
+        Cell::new(match config.block_trigger {
+            BlockTrigger::Time => String::from("Time"),
+            BlockTrigger::Size => format!("Size ({} txs)", config.seal_threshold),
+            BlockTrigger::Hybrid => format!("Hybrid ({} txs)", config.seal_threshold),
+            BlockTrigger::Manual => String::from("Manual"),
+        }),
+    ]);
+    table.add_row(vec![
+        Cell::new("Max Block Transactions"),
+        Cell::new(display_limit(config.block_limits.max_transactions)),
+    ]);
+    table.add_row(vec![
+        Cell::new("Max Block Bytes"),
+        Cell::new(display_limit(config.block_limits.max_bytes)),
+    ]);
+    table.add_row(vec![
+        Cell::new("Blocks Per Epoch"),
+        Cell::new(config.block_limits.max_blocks_per_epoch),
+    ]);
+    table.add_row(vec![
+        Cell::new("Empty Blocks"),
+        Cell::new(match config.empty_block_policy {
+            EmptyBlockPolicy::Always => String::from("Always"),
+            EmptyBlockPolicy::Heartbeat => format!("Heartbeat ({}s)", config.heartbeat_interval),
+            EmptyBlockPolicy::Skip => String::from("Skip"),
+        }),
+    ]);
+    table.add_row(vec![
+        Cell::new("Nonce Limit"),
+        Cell::new(config.nonce_limit),
+    ]);
+    table.add_row(vec![
+        Cell::new("Hash Algorithm"),
+        Cell::new(match config.chain.hash_algorithm() {
+            Some(hash_algorithm) => hash_algorithm.to_string(),
+            None => String::from("Adopted with the first blocks pulled"),
+        }),
+    ]);
+    table.add_row(vec![
+        Cell::new("Admin API"),
+        Cell::new(match config.admin_token {
+            Some(_) => "Enabled",
+            None => "Disabled",
+        }),
+    ]);
+    table.add_row(vec![
+        Cell::new("Assets"),
+        Cell::new(match &config.assets_dir {
+            Some(dir) => format!("Embedded (override: {})", dir.display()),
+            None => String::from("Embedded"),
+        }),
+    ]);
+    table.add_row(vec![
+        Cell::new("Backup Directory"),
+        Cell::new(config.backup_dir.display()),
+    ]);
     table.add_row(vec![
         Cell::new("Mode"),
-        Cell::new(match mode {
+        Cell::new(match config.mode {
             Mode::Full => "Full",
             Mode::FactoryOnly => "Factory Only",
             Mode::QueryOnly => "Query Only",
         }),
     ]);
+    table.add_row(vec![
+        Cell::new("Primary URL"),
+        Cell::new(config.primary_url.as_deref().unwrap_or("-")),
+    ]);
+    table.add_row(vec![
+        Cell::new("Consensus"),
+        Cell::new(match &config.producers {
+            Some(producers) => format!("Proof of Authority ({} producers)", producers.len()),
+            None => String::from("None"),
+        }),
+    ]);
+    table.add_row(vec![
+        Cell::new("Producer Key"),
+        Cell::new(match (&config.producer_key, &config.public_key) {
+            (Some(path), Some(public_key)) => format!("{} ({public_key})", path.display()),
+            _ => String::from("-"),
+        }),
+    ]);
+    table.add_row(vec![
+        Cell::new("Peers"),
+        Cell::new(if config.peers.is_empty() {
+            String::from("-")
+        } else {
+            config.peers.join(", ")
+        }),
+    ]);
 
     println!("{table}");
 }
+
+fn display_limit(limit: Option<usize>) -> String {
+    match limit {
+        Some(limit) => limit.to_string(),
+        None => String::from("Unlimited"),
+    }
+}
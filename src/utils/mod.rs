@@ -1,9 +1,11 @@
 mod display_configuration;
 mod get_random_nonce;
 mod get_unix_timestamp_now;
+mod parse_block_time;
 
 pub use display_configuration::*;
 pub use get_random_nonce::*;
+pub use parse_block_time::*;
 
 #[allow(unused_imports)]
 pub use get_unix_timestamp_now::*;
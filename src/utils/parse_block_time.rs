@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+// shortest block time the factory accepts
+const MIN_BLOCK_TIME: Duration = Duration::from_millis(1);
+
+/// Parses a block time like `250ms`, `2s` or `1m 30s`,
+/// a plain number is read as whole seconds
+pub fn parse_block_time(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+
+    let block_time = match value.parse::<u64>() {
+        Ok(secs) => Duration::from_secs(secs),
+        Err(_) => humantime::parse_duration(value).map_err(|err| err.to_string())?,
+    };
+
+    if block_time < MIN_BLOCK_TIME {
+        return Err(format!(
+            "block time must be at least {}",
+            humantime::format_duration(MIN_BLOCK_TIME)
+        ));
+    }
+
+    Ok(block_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_unit_suffixes() {
+        assert_eq!(parse_block_time("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_block_time("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_block_time("1m 30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_block_time("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_block_time(" 5s "), Ok(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn reads_plain_numbers_as_seconds() {
+        assert_eq!(parse_block_time("3"), Ok(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn refuses_block_times_below_the_minimum() {
+        for value in ["0", "0s", "0ms", "500us"] {
+            assert!(
+                parse_block_time(value).unwrap_err().contains("at least"),
+                "{value}"
+            );
+        }
+        assert_eq!(
+            parse_block_time(&format!("{}ms", MIN_BLOCK_TIME.as_millis())),
+            Ok(MIN_BLOCK_TIME)
+        );
+    }
+
+    #[test]
+    fn refuses_overflowing_block_times() {
+        assert!(parse_block_time("99999999999999999999").is_err());
+        assert!(parse_block_time("99999999999999999999s").is_err());
+        assert!(parse_block_time("999999999999999999y").is_err());
+    }
+
+    #[test]
+    fn refuses_malformed_block_times() {
+        for value in ["", "fast", "-1", "1.5.2s", "5 parsecs", "s"] {
+            assert!(parse_block_time(value).is_err(), "{value}");
+        }
+    }
+}
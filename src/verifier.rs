@@ -0,0 +1,324 @@
+use anyhow::{bail, Result};
+
+use crate::authority::Producers;
+use crate::encoding::LEGACY_HEADER_VERSION;
+use crate::error::BlockFactoryError;
+use crate::hasher::{chain_hash_algorithm, HashAlgorithm};
+use crate::keys::check_signature;
+use crate::persistence::{
+    models::{BlockData, TransactionData},
+    sqlite::SqlitePersistence,
+    walker::ChainWalker,
+};
+
+/// Checks blocks one by one from genesis upwards,
+/// recomputing hashes and following the chain linkage
+#[derive(Debug, Default)]
+pub struct ChainVerifier {
+    // last block that passed, the next one must link to it
+    tip: Option<BlockData>,
+    blocks: u32,
+    transactions: u32,
+    // the chain's, recorded at genesis
+    hash_algorithm: HashAlgorithm,
+    // proof-of-authority chains check each block was sealed in turn
+    producers: Option<Producers>,
+}
+
+impl ChainVerifier {
+    /// Checks from genesis, recomputing hashes with `hash_algorithm`
+    pub fn new(hash_algorithm: HashAlgorithm) -> Self {
+        Self {
+            hash_algorithm,
+            ..Self::default()
+        }
+    }
+
+    /// Continues checking after `tip`, a block already verified and stored
+    pub fn resume(tip: BlockData, hash_algorithm: HashAlgorithm) -> Self {
+        Self {
+            tip: Some(tip),
+            hash_algorithm,
+            ..Self::default()
+        }
+    }
+
+    /// Also checks each block is signed by the producer whose turn it was
+    pub fn with_producers(mut self, producers: Option<Producers>) -> Self {
+        self.producers = producers;
+        self
+    }
+
+    /// Last verified block
+    pub fn tip(&self) -> Option<&BlockData> {
+        self.tip.as_ref()
+    }
+
+    pub fn blocks(&self) -> u32 {
+        self.blocks
+    }
+
+    pub fn transactions(&self) -> u32 {
+        self.transactions
+    }
+
+    /// Checks that `block` follows the last verified block
+    /// and that `transactions` are its own, in block order
+    pub fn check(&mut self, block: &BlockData, transactions: &[TransactionData]) -> Result<()> {
+        let (expected_height, expected_prev_hash, min_version) = match &self.tip {
+            Some(tip) => (tip.height + 1, tip.hash.as_str(), tip.version),
+            // genesis is at height 1 and links to nothing
+            None => (1, "", LEGACY_HEADER_VERSION),
+        };
+
+        if block.height != expected_height {
+            invalid(block, format!("expected height {expected_height}"))?;
+        }
+        if block.prev_block_hash != expected_prev_hash {
+            invalid(
+                block,
+                format!(
+                    "previous block hash {} doesn't link to {expected_prev_hash}",
+                    block.prev_block_hash
+                ),
+            )?;
+        }
+        // a chain moves to newer header formats, never back
+        if block.version < min_version {
+            invalid(
+                block,
+                format!(
+                    "header version {} follows version {min_version}",
+                    block.version
+                ),
+            )?;
+        }
+        match block.computed_hash(self.hash_algorithm, transactions) {
+            None => invalid(
+                block,
+                format!("unsupported header version {}", block.version),
+            )?,
+            Some(hash) if hash != block.hash => invalid(
+                block,
+                format!("hash {} doesn't match its header", block.hash),
+            )?,
+            Some(_) => {}
+        }
+
+        // signed blocks must hold up even when no producer is expected
+        let signed = block.producer.is_some() || block.signature.is_some();
+        let signature = match &self.producers {
+            Some(producers) => producers.check(block),
+            None if signed => check_signature(block),
+            None => Ok(()),
+        };
+        if let Err(reason) = signature {
+            invalid(block, reason)?;
+        }
+
+        for (position, tx) in transactions.iter().enumerate() {
+            if tx.block_height != block.height || tx.position as usize != position {
+                invalid(block, format!("transaction {} is out of place", tx.hash))?;
+            }
+            if tx
+                .computed_hash(self.hash_algorithm, block.version)
+                .as_deref()
+                != Some(tx.hash.as_str())
+            {
+                invalid(
+                    block,
+                    format!("transaction hash {} doesn't match its fields", tx.hash),
+                )?;
+            }
+        }
+
+        self.tip = Some(block.clone());
+        self.blocks += 1;
+        self.transactions += transactions.len() as u32;
+
+        Ok(())
+    }
+}
+
+/// Checks a whole stored chain, from genesis to tip, with its recorded
+/// hash algorithm and the producer rotation if it's a proof-of-authority chain
+pub async fn verify_chain(
+    persistence: &SqlitePersistence,
+    producers: Option<Producers>,
+) -> Result<ChainVerifier> {
+    let hash_algorithm = chain_hash_algorithm(persistence).await?;
+    let mut walker = ChainWalker::new(persistence);
+    let mut verifier = ChainVerifier::new(hash_algorithm).with_producers(producers);
+    while let Some(sealed) = walker.next().await? {
+        verifier.check(&sealed.block, &sealed.transactions)?;
+    }
+
+    if verifier.tip().is_none() {
+        bail!(BlockFactoryError::MissingGenesis);
+    }
+
+    Ok(verifier)
+}
+
+fn invalid(block: &BlockData, reason: String) -> Result<()> {
+    bail!(BlockFactoryError::InvalidBlock {
+        height: block.height,
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::BLOCK_HEADER_VERSION;
+    use crate::keys::NodeKey;
+    use crate::persistence::NodePersistency;
+
+    // stores genesis, a block with a transaction and an empty block, all signed
+    async fn stored_chain() -> SqlitePersistence {
+        let persistence = SqlitePersistence::connect("sqlite::memory:").await.unwrap();
+        let key = NodeKey::from_secret([1; 32]);
+
+        let mut prev_block_hash = String::new();
+        for height in 1..=3 {
+            let id = format!("0192a0c4-8e00-7000-8000-{height:012}");
+            let transactions = match height {
+                2 => {
+                    let tx_id = String::from("0192a0c4-8e00-7000-8000-100000000000");
+                    let hash = TransactionData::get_hash(
+                        HashAlgorithm::Sha256,
+                        &tx_id,
+                        "alice",
+                        "bob",
+                        b"hi",
+                        1,
+                    );
+                    vec![TransactionData {
+                        id: tx_id,
+                        hash,
+                        from: String::from("alice"),
+                        to: String::from("bob"),
+                        instruction: b"hi".to_vec(),
+                        fee: 1,
+                        block_height: height,
+                        position: 0,
+                    }]
+                }
+                _ => vec![],
+            };
+            let transaction_hashes: Vec<&str> =
+                transactions.iter().map(|tx| tx.hash.as_str()).collect();
+
+            let mut block = BlockData {
+                version: BLOCK_HEADER_VERSION,
+                hash: BlockData::get_hash(
+                    HashAlgorithm::Sha256,
+                    &id,
+                    height,
+                    height,
+                    &prev_block_hash,
+                    &transaction_hashes,
+                ),
+                id,
+                nonce: height,
+                height,
+                prev_block_hash,
+                producer: None,
+                signature: None,
+            };
+            key.sign(&mut block);
+            persistence
+                .store_sealed_block_data(&block, &transactions)
+                .await
+                .unwrap();
+            prev_block_hash = block.hash;
+        }
+
+        persistence
+    }
+
+    // corrupts the stored chain with `statement`, returns why it no longer verifies
+    async fn verify_corrupted(statement: &str) -> String {
+        let persistence = stored_chain().await;
+        sqlx::query(statement)
+            .execute(&persistence.pool)
+            .await
+            .unwrap();
+
+        let err = verify_chain(&persistence, None).await.unwrap_err();
+        err.to_string()
+    }
+
+    #[tokio::test]
+    async fn verifies_an_intact_chain() {
+        let persistence = stored_chain().await;
+
+        let verifier = verify_chain(&persistence, None).await.unwrap();
+
+        assert_eq!(verifier.blocks(), 3);
+        assert_eq!(verifier.transactions(), 1);
+        assert_eq!(verifier.tip().map(|tip| tip.height), Some(3));
+    }
+
+    #[tokio::test]
+    async fn reports_a_tampered_header() {
+        let reason = verify_corrupted("UPDATE block_data SET nonce = 99 WHERE height = 2").await;
+
+        assert!(reason.starts_with("invalid block at height 2: hash"));
+        assert!(reason.ends_with("doesn't match its header"));
+    }
+
+    #[tokio::test]
+    async fn reports_a_tampered_transaction() {
+        let reason = verify_corrupted("UPDATE transaction_data SET fee = 1000").await;
+
+        assert!(reason.starts_with("invalid block at height 2: transaction hash"));
+    }
+
+    #[tokio::test]
+    async fn reports_a_removed_transaction() {
+        let reason = verify_corrupted("DELETE FROM transaction_data").await;
+
+        assert!(reason.starts_with("invalid block at height 2: hash"));
+    }
+
+    #[tokio::test]
+    async fn reports_a_broken_link() {
+        let reason =
+            verify_corrupted("UPDATE block_data SET prev_block_hash = 'ab' WHERE height = 3").await;
+
+        assert!(reason.starts_with("invalid block at height 3: previous block hash ab"));
+    }
+
+    #[tokio::test]
+    async fn reports_a_bad_signature() {
+        let reason = verify_corrupted(
+            "UPDATE block_data SET signature = (SELECT signature FROM block_data WHERE height = 3) \
+             WHERE height = 2",
+        )
+        .await;
+
+        assert!(reason.starts_with("invalid block at height 2: signature"));
+        assert!(reason.ends_with("doesn't match the block hash"));
+    }
+
+    #[tokio::test]
+    async fn reports_a_version_regression() {
+        let reason = verify_corrupted("UPDATE block_data SET version = 1 WHERE height = 3").await;
+
+        assert_eq!(
+            reason,
+            "invalid block at height 3: header version 1 follows version 2"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_an_unknown_version() {
+        let reason = verify_corrupted("UPDATE block_data SET version = 9 WHERE height = 1").await;
+
+        assert_eq!(
+            reason,
+            "invalid block at height 1: unsupported header version 9"
+        );
+    }
+}